@@ -0,0 +1,803 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Error, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use mio::net::UdpSocket;
+use mio::{Events, Interest, Poll, Token, Waker};
+use tracing::{debug, info, info_span, warn};
+
+use common::{
+    parse_message, receive_message, AckData, ConnectionData, FileData, FileReceiver, GenericError,
+    GetData, Message, MessageCreationError, NoopObserver, SendOptions, Sender, TransferInfo,
+    TransferObserver, TransferStats, MAX_DATAGRAM_SIZE,
+};
+
+use crate::directory::walk_directory;
+use crate::{DirectorySummary, Filename};
+
+/// Token do socket TCP de controle no poll.
+const CONTROL: Token = Token(0);
+/// Token do socket UDP de dados no poll.
+const DATA: Token = Token(1);
+/// Token usado por `TransferHandle` para acordar o laço de eventos.
+const WAKER: Token = Token(2);
+/// Tempo máximo sem blocos do servidor no download, quando `SendOptions::timeout` não é definido. É maior que o RTO
+/// máximo do servidor, para que uma retransmissão depois de várias perdas ainda chegue a tempo.
+const DEFAULT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Conexão com um servidor, pronta para o envio ou o download de arquivos. Os arquivos são transferidos um por vez,
+/// na mesma sessão e com a mesma porta UDP do servidor, até que `close` seja chamado ou o cliente seja descartado.
+/// Depois de um erro, o servidor encerra a sessão, e é preciso conectar novamente.
+///
+/// ```no_run
+/// use cliente::{Client, SendOptions};
+///
+/// let mut client = Client::connect("127.0.0.1:5000")?;
+/// client.send_file("a.txt", &SendOptions::default())?;
+/// client.send_file("b.txt", &SendOptions::default())?;
+/// client.close()?;
+/// # Ok::<(), common::GenericError>(())
+/// ```
+pub struct Client {
+    stream: TcpStream,
+    server_address: SocketAddr,
+    /// Endereço do socket UDP do servidor.
+    data_address: SocketAddr,
+    /// Resposta do servidor ao "Hello", com a sessão e a janela de recepção.
+    connection: ConnectionData,
+    observer: Box<dyn TransferObserver>,
+    poll: Poll,
+    waker: Arc<Waker>,
+    requests: Arc<Requests>,
+}
+
+/// Pedidos feitos por `TransferHandle`, atendidos pelo laço de eventos da transferência.
+#[derive(Default)]
+struct Requests {
+    aborted: AtomicBool,
+    paused: AtomicBool,
+}
+
+/// Controla a transferência de um `Client` a partir de outra thread, como no tratamento de sinais.
+#[derive(Clone)]
+pub struct TransferHandle {
+    requests: Arc<Requests>,
+    waker: Arc<Waker>,
+}
+
+impl TransferHandle {
+    /// Pede o cancelamento da transferência: o servidor recebe a mensagem "Abort" e remove o arquivo parcial, e
+    /// `send_file` ou `fetch_file` retornam um erro. Durante o handshake, o cancelamento só tem efeito quando o servidor
+    /// responde.
+    ///
+    /// No download, o arquivo parcial removido é o local.
+    pub fn abort(&self) -> Result<(), Error> {
+        self.requests.aborted.store(true, Ordering::SeqCst);
+        self.waker.wake()
+    }
+
+    /// Suspende o envio dos blocos e avisa o servidor com a mensagem "Pause", mantendo a sessão e a janela. Um pedido
+    /// feito antes do início do envio dos blocos vale a partir dele. Não tem efeito no download.
+    pub fn pause(&self) -> Result<(), Error> {
+        self.requests.paused.store(true, Ordering::SeqCst);
+        self.waker.wake()
+    }
+
+    /// Retoma o envio pausado por `pause`.
+    pub fn resume(&self) -> Result<(), Error> {
+        self.requests.paused.store(false, Ordering::SeqCst);
+        self.waker.wake()
+    }
+
+    /// Se há um pedido de pausa em vigor.
+    pub fn is_paused(&self) -> bool {
+        self.requests.paused.load(Ordering::SeqCst)
+    }
+}
+
+impl Client {
+    /// Conecta ao servidor e faz o handshake, obtendo a porta UDP e a janela de recepção do servidor.
+    pub fn connect<A: ToSocketAddrs>(address: A) -> Result<Client, GenericError> {
+        let stream = GenericError::transform_io(TcpStream::connect(address))?;
+        Client::handshake(stream)
+    }
+
+    /// Como `connect`, mas desiste caso a conexão ou a resposta ao handshake demorem mais que `timeout`.
+    pub fn connect_timeout(
+        address: &SocketAddr,
+        timeout: Duration,
+    ) -> Result<Client, GenericError> {
+        let stream = GenericError::transform_io(TcpStream::connect_timeout(address, timeout))?;
+        GenericError::transform_io(stream.set_read_timeout(Some(timeout)))?;
+        Client::handshake(stream)
+    }
+
+    fn handshake(mut stream: TcpStream) -> Result<Client, GenericError> {
+        let server_address = GenericError::transform_io(stream.peer_addr())?;
+        let poll = GenericError::transform_io(Poll::new())?;
+        let waker = Arc::new(GenericError::transform_io(Waker::new(
+            poll.registry(),
+            WAKER,
+        ))?);
+
+        GenericError::transform_io(stream.write_all(&Message::Hello.encode()))?;
+
+        let connection = match receive_reply(&mut stream)? {
+            Message::Connection(connection) => connection,
+            Message::Error(code) => return Err(GenericError::Protocol(code)),
+            _ => return Err(logic_error("Não foi possível obter a porta UDP")),
+        };
+
+        debug!(
+            server = %server_address,
+            udp_port = connection.port,
+            session_id = connection.session_id,
+            receive_window = connection.receive_window,
+            "Handshake concluído"
+        );
+
+        Ok(Client {
+            stream,
+            server_address,
+            data_address: SocketAddr::new(server_address.ip(), connection.port as u16),
+            connection,
+            observer: Box::new(NoopObserver),
+            poll,
+            waker,
+            requests: Arc::new(Requests::default()),
+        })
+    }
+
+    /// Permite cancelar ou pausar a transferência a partir de outra thread, enquanto `send_file` ou `fetch_file`
+    /// executam.
+    pub fn transfer_handle(&self) -> TransferHandle {
+        TransferHandle {
+            requests: Arc::clone(&self.requests),
+            waker: Arc::clone(&self.waker),
+        }
+    }
+
+    /// Define o observador notificado do andamento do envio, como `ProgressBar`.
+    pub fn with_observer<O: TransferObserver + 'static>(mut self, observer: O) -> Client {
+        self.observer = Box::new(observer);
+        self
+    }
+
+    /// Envia o arquivo em `path` e espera a confirmação do servidor de que ele foi escrito. O arquivo é salvo no
+    /// servidor com o mesmo nome, que deve atender às restrições de `Filename`. Retorna as estatísticas do envio.
+    ///
+    /// Se o envio falhar por um erro local, como a leitura do arquivo, ou for cancelado por `TransferHandle`, o servidor
+    /// é avisado com a mensagem "Abort" para encerrar a sessão e remover o arquivo parcial.
+    pub fn send_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &SendOptions,
+    ) -> Result<TransferStats, GenericError> {
+        self.upload(path.as_ref(), None, options)
+    }
+
+    /// Envia recursivamente os arquivos de `directory`, um por vez na mesma sessão, e o servidor recria a árvore no
+    /// seu diretório de saída. Os arquivos são anunciados com a mensagem "InfoPath", com o caminho relativo a
+    /// `directory`, sem as restrições de `Filename`. Links simbólicos e diretórios vazios não são enviados.
+    ///
+    /// O envio para no primeiro erro, tratado como em `send_file`; os arquivos já enviados permanecem no servidor.
+    pub fn send_directory<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        options: &SendOptions,
+    ) -> Result<DirectorySummary, GenericError> {
+        let started_at = Instant::now();
+        let entries = walk_directory(directory.as_ref())?;
+        info!(
+            directory = %directory.as_ref().display(),
+            files = entries.len(),
+            "Enviando o diretório"
+        );
+
+        let mut summary = DirectorySummary::default();
+        for entry in &entries {
+            match self.upload(&entry.path, Some(&entry.relative_path), options) {
+                Ok(stats) => summary.add(&stats),
+                Err(e) => {
+                    warn!(
+                        "{} de {} arquivos enviados antes da falha em {}",
+                        summary.files,
+                        entries.len(),
+                        entry.relative_path
+                    );
+                    return Err(e);
+                }
+            }
+        }
+        summary.elapsed = started_at.elapsed();
+
+        Ok(summary)
+    }
+
+    /// Envia o arquivo em `path`, anunciado com o caminho relativo `relative_path` ou, sem ele, com o nome do arquivo.
+    fn upload(
+        &mut self,
+        path: &Path,
+        relative_path: Option<&str>,
+        options: &SendOptions,
+    ) -> Result<TransferStats, GenericError> {
+        let _span = info_span!(
+            "transfer",
+            peer = %self.server_address,
+            file = %path.display()
+        )
+        .entered();
+
+        let file_contents = match self
+            .check_aborted()
+            .and_then(|()| self.announce_file(path, relative_path, options))
+        {
+            Ok(file_contents) => file_contents,
+            Err(e) => {
+                if !matches!(e, GenericError::Protocol(_)) {
+                    let _ = self.stream.write_all(&Message::Abort.encode());
+                }
+                self.observer.on_error(&e);
+                return Err(e);
+            }
+        };
+
+        let observer = std::mem::replace(&mut self.observer, Box::new(NoopObserver));
+        let mut sender =
+            Sender::new(file_contents, &self.connection, options).with_observer(observer);
+        let result = self.transfer_file(&mut sender);
+        match &result {
+            Ok(()) => sender.observer().on_complete(),
+            Err(e) => sender.observer().on_error(e),
+        }
+
+        let stats = sender.stats();
+        self.observer = sender.into_observer();
+        result.map(|()| stats)
+    }
+
+    /// Baixa o arquivo `filename` do diretório de saída do servidor e salva-o em `destination`. O servidor envia os
+    /// blocos via UDP com a mesma janela deslizante do envio, e o cliente confirma-os com acks e, depois de escrever o
+    /// arquivo, com a mensagem de fim. Retorna as estatísticas do download.
+    ///
+    /// Só podem ser baixados os arquivos na raiz do diretório de saída: os enviados em subdiretórios por
+    /// `send_directory` não podem ser pedidos, já que `filename` segue o formato de `Filename`, sem diretórios.
+    ///
+    /// Se o arquivo não existir no servidor, retorna o erro `ErrorCode::FileNotFound`. Se o download falhar ou for
+    /// cancelado, o arquivo parcial em `destination` é removido e, caso o erro não tenha vindo do servidor, ele é
+    /// avisado com a mensagem "Abort".
+    pub fn fetch_file<P: AsRef<Path>>(
+        &mut self,
+        filename: &str,
+        destination: P,
+        options: &SendOptions,
+    ) -> Result<TransferStats, GenericError> {
+        let destination = destination.as_ref();
+        let _span = info_span!(
+            "download",
+            peer = %self.server_address,
+            file = %filename
+        )
+        .entered();
+
+        let mut download = match self
+            .check_aborted()
+            .and_then(|()| self.request_file(filename, destination, options))
+        {
+            Ok(download) => download,
+            Err(e) => {
+                if !matches!(e, GenericError::Protocol(_)) {
+                    let _ = self.stream.write_all(&Message::Abort.encode());
+                }
+                self.observer.on_error(&e);
+                return Err(e);
+            }
+        };
+
+        let mut observer = std::mem::replace(&mut self.observer, Box::new(NoopObserver));
+        let result = self.download_file(&mut download, &mut *observer);
+        match &result {
+            Ok(()) => observer.on_complete(),
+            Err(e) => {
+                if let Err(remove_error) = fs::remove_file(destination) {
+                    debug!("Falha ao remover o arquivo parcial: {}", remove_error);
+                }
+                observer.on_error(e);
+            }
+        }
+
+        self.observer = observer;
+        result.map(|()| download.stats)
+    }
+
+    /// Encerra a sessão com a mensagem "Close", depois da última transferência. Descartar o cliente sem chamar `close`
+    /// também encerra a sessão, pelo fechamento da conexão.
+    pub fn close(mut self) -> Result<(), GenericError> {
+        GenericError::transform_io(self.stream.write_all(&Message::Close.encode()))
+    }
+
+    /// Pede o arquivo com a mensagem "Get", espera o anúncio do servidor com a mensagem "InfoFile" e cria o arquivo
+    /// local.
+    fn request_file(
+        &mut self,
+        filename: &str,
+        destination: &Path,
+        options: &SendOptions,
+    ) -> Result<Download, GenericError> {
+        options.validate().map_err(|e| logic_error(&e))?;
+        if let Some(timeout) = options.timeout {
+            GenericError::transform_io(self.stream.set_read_timeout(Some(timeout)))?;
+        }
+        let filename = Filename::new(Some(filename.to_string())).map_err(logic_error)?;
+
+        let bind_address: IpAddr = match self.data_address.ip() {
+            IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let socket = GenericError::transform_io(UdpSocket::bind(SocketAddr::new(bind_address, 0)))?;
+        let port = GenericError::transform_io(socket.local_addr())?.port();
+        let receive_window = options.window.unwrap_or(self.connection.receive_window);
+
+        GenericError::transform_io(
+            self.stream.write_all(
+                &Message::Get(GetData {
+                    filename: filename.filename.clone(),
+                    chunk_size: options.chunk_size as u16,
+                    receive_window,
+                    port,
+                })
+                .encode(),
+            ),
+        )?;
+
+        let file_data = match receive_reply(&mut self.stream)? {
+            Message::InfoFile(file_data) if file_data.filename == filename.filename => file_data,
+            Message::Error(code) => return Err(GenericError::Protocol(code)),
+            _ => return Err(logic_error("Tipo de mensagem inesperado")),
+        };
+        if file_data.chunk_size == 0 {
+            return Err(logic_error("Tamanho de bloco inválido"));
+        }
+        info!(
+            file_size = file_data.file_size,
+            "Pronto para iniciar o recebimento do arquivo."
+        );
+
+        let file = GenericError::transform_io(File::create(destination))?;
+        self.observer.on_handshake(&TransferInfo {
+            peer: self.server_address,
+            filename: file_data.filename.clone(),
+            file_size: file_data.file_size,
+        });
+
+        Ok(Download {
+            socket,
+            data_address: self.data_address,
+            session_id: self.connection.session_id,
+            timeout: options.timeout.unwrap_or(DEFAULT_DOWNLOAD_TIMEOUT),
+            receiver: FileReceiver::new(file_data.file_size, file_data.chunk_size, receive_window),
+            receive_window,
+            file: BufWriter::new(file),
+            stats: TransferStats {
+                file_size: file_data.file_size,
+                ..TransferStats::default()
+            },
+            file_data,
+        })
+    }
+
+    /// Recebe os blocos do arquivo pedido. Em caso de erro que não tenha vindo do servidor, envia a mensagem "Abort".
+    fn download_file(
+        &mut self,
+        download: &mut Download,
+        observer: &mut dyn TransferObserver,
+    ) -> Result<(), GenericError> {
+        self.run_event_loop(|poll, stream, requests| {
+            receive_chunks(poll, stream, requests, download, observer)
+        })
+    }
+
+    /// Envia a mensagem InfoFile e espera a confirmação do servidor. Retorna o conteúdo do arquivo.
+    fn announce_file(
+        &mut self,
+        path: &Path,
+        relative_path: Option<&str>,
+        options: &SendOptions,
+    ) -> Result<Vec<u8>, GenericError> {
+        options.validate().map_err(|e| logic_error(&e))?;
+        if let Some(timeout) = options.timeout {
+            GenericError::transform_io(self.stream.set_read_timeout(Some(timeout)))?;
+        }
+
+        let filename = match relative_path {
+            Some(relative_path) => relative_path.to_string(),
+            None => {
+                let filename = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .map(|name| name.to_string());
+                Filename::new(filename).map_err(logic_error)?.filename
+            }
+        };
+
+        let file_contents = GenericError::transform_io(std::fs::read(path))?;
+        debug!(file_size = file_contents.len(), "Arquivo lido");
+        let file_data = FileData {
+            filename,
+            file_size: file_contents.len() as u64,
+            chunk_size: options.chunk_size as u16,
+        };
+        let transfer = TransferInfo {
+            peer: self.server_address,
+            filename: file_data.filename.clone(),
+            file_size: file_data.file_size,
+        };
+        let message = match relative_path {
+            Some(_) => GenericError::transform_logic(Message::info_path(file_data))?,
+            None => Message::InfoFile(file_data),
+        };
+        GenericError::transform_io(self.stream.write_all(&message.encode()))?;
+
+        match receive_reply(&mut self.stream)? {
+            Message::Ok => info!("Pronto para iniciar transmissão do arquivo."),
+            Message::Error(code) => return Err(GenericError::Protocol(code)),
+            _ => return Err(logic_error("Tipo de mensagem inesperado")),
+        }
+        self.observer.on_handshake(&transfer);
+
+        Ok(file_contents)
+    }
+
+    fn check_aborted(&self) -> Result<(), GenericError> {
+        if self.requests.aborted.load(Ordering::SeqCst) {
+            Err(aborted_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Cria o socket UDP e envia os blocos.
+    fn transfer_file(&mut self, sender: &mut Sender) -> Result<(), GenericError> {
+        let data_address = self.data_address;
+        self.run_event_loop(|poll, stream, requests| {
+            transfer_chunks(poll, stream, data_address, requests, sender)
+        })
+    }
+
+    /// Executa o laço de eventos de uma transferência com uma cópia não bloqueante do socket TCP e, ao final, devolve
+    /// o socket ao modo bloqueante usado no anúncio do próximo arquivo. Em caso de erro que não tenha vindo do
+    /// servidor, envia a mensagem "Abort".
+    fn run_event_loop<F>(&mut self, event_loop: F) -> Result<(), GenericError>
+    where
+        F: FnOnce(&mut Poll, &mut mio::net::TcpStream, &Requests) -> Result<(), GenericError>,
+    {
+        let stream = GenericError::transform_io(self.stream.try_clone())?;
+        GenericError::transform_io(stream.set_nonblocking(true))?;
+        let mut stream = mio::net::TcpStream::from_std(stream);
+
+        let result = event_loop(&mut self.poll, &mut stream, &self.requests);
+        if let Err(e) = &result {
+            if !matches!(e, GenericError::Protocol(_)) {
+                debug!("Avisando o servidor do cancelamento da transferência");
+                let _ = stream.write(&Message::Abort.encode());
+            }
+        }
+
+        // A cópia compartilha o socket com `self.stream`, então precisa ser removida do poll explicitamente.
+        let _ = self.poll.registry().deregister(&mut stream);
+        let restored = self.stream.set_nonblocking(false);
+        result.and(GenericError::transform_io(restored))
+    }
+}
+
+/// Arquivo sendo baixado do servidor.
+struct Download {
+    /// Socket UDP em que os blocos são recebidos, cuja porta foi informada na mensagem "Get".
+    socket: UdpSocket,
+    /// Endereço do socket UDP do servidor, única origem aceita para os blocos.
+    data_address: SocketAddr,
+    session_id: u32,
+    /// Tempo máximo sem blocos do servidor, `DEFAULT_DOWNLOAD_TIMEOUT` se não for definido nas opções.
+    timeout: Duration,
+    file_data: FileData,
+    receiver: FileReceiver,
+    /// Janela de recepção informada na mensagem "Get" e repetida nos acks.
+    receive_window: u16,
+    file: BufWriter<File>,
+    stats: TransferStats,
+}
+
+/// Laço de eventos do download: recebe os blocos, escreve-os em ordem no arquivo e confirma-os ao servidor. Termina
+/// com a mensagem de fim, enviada depois que o último bloco é escrito.
+fn receive_chunks(
+    poll: &mut Poll,
+    stream: &mut mio::net::TcpStream,
+    requests: &Requests,
+    download: &mut Download,
+    observer: &mut dyn TransferObserver,
+) -> Result<(), GenericError> {
+    GenericError::transform_io(
+        poll.registry()
+            .register(stream, CONTROL, Interest::READABLE),
+    )?;
+    GenericError::transform_io(poll.registry().register(
+        &mut download.socket,
+        DATA,
+        Interest::READABLE,
+    ))?;
+
+    let started_at = Instant::now();
+    let mut last_activity = started_at;
+    let mut events = Events::with_capacity(16);
+    let mut received_bytes: Vec<u8> = Vec::new();
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+
+    loop {
+        if requests.aborted.load(Ordering::SeqCst) {
+            return Err(aborted_error());
+        }
+
+        let deadline = last_activity + download.timeout;
+        if deadline <= Instant::now() {
+            return Err(GenericError::IO(Error::new(
+                ErrorKind::TimedOut,
+                "O servidor não respondeu a tempo",
+            )));
+        }
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if let Err(e) = poll.poll(&mut events, Some(timeout)) {
+            if e.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(GenericError::IO(e));
+        }
+
+        for event in events.iter() {
+            match event.token() {
+                CONTROL => {
+                    let connection_closed =
+                        GenericError::transform_io(read_available(stream, &mut received_bytes))?;
+
+                    while let Some((message, length)) =
+                        GenericError::transform_logic(parse_message(&received_bytes))?
+                    {
+                        received_bytes.drain(..length);
+                        if let Message::Error(code) = message {
+                            return Err(GenericError::Protocol(code));
+                        }
+                    }
+
+                    if connection_closed {
+                        return Err(GenericError::IO(Error::new(
+                            ErrorKind::ConnectionAborted,
+                            "Conexão fechada",
+                        )));
+                    }
+                }
+                DATA => loop {
+                    let (bytes_read, source) = match download.socket.recv_from(&mut buffer) {
+                        Ok(received) => received,
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(GenericError::IO(e)),
+                    };
+                    // Datagramas de outras origens ou sessões são descartados.
+                    let chunk = match Message::new(&buffer, bytes_read) {
+                        Ok(Message::File(chunk))
+                            if source == download.data_address
+                                && chunk.session_id == download.session_id =>
+                        {
+                            chunk
+                        }
+                        _ => continue,
+                    };
+                    last_activity = Instant::now();
+
+                    let outcome = download
+                        .receiver
+                        .on_chunk(chunk.sequence_number, chunk.data);
+                    download.stats.chunks_received += 1;
+                    if outcome.duplicate {
+                        download.stats.duplicates_received += 1;
+                        observer.on_retransmit(chunk.sequence_number);
+                    }
+                    if outcome.dropped {
+                        download.stats.out_of_window_drops += 1;
+                    }
+
+                    for data in outcome.ready_to_write {
+                        GenericError::transform_io(download.file.write_all(&data))?;
+                    }
+                    for ack in outcome.acks {
+                        let message = Message::Ack(AckData {
+                            sequence_number: ack,
+                            receive_window: download.receive_window,
+                        });
+                        GenericError::transform_io(stream.write_all(&message.encode()))?;
+                        download.stats.acks += 1;
+                        let acked_bytes = ((ack as u64 + 1) * download.file_data.chunk_size as u64)
+                            .min(download.file_data.file_size);
+                        observer.on_chunk_acked(ack, acked_bytes);
+                    }
+
+                    if outcome.finished {
+                        GenericError::transform_io(download.file.flush())?;
+                        GenericError::transform_io(stream.write_all(&Message::End.encode()))?;
+                        download.stats.elapsed = started_at.elapsed();
+                        info!("Arquivo recebido com sucesso.");
+                        return Ok(());
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Laço de eventos do envio dos blocos, até a confirmação do servidor.
+fn transfer_chunks(
+    poll: &mut Poll,
+    stream: &mut mio::net::TcpStream,
+    data_address: SocketAddr,
+    requests: &Requests,
+    sender: &mut Sender,
+) -> Result<(), GenericError> {
+    let bind_address: IpAddr = match data_address.ip() {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let mut socket = GenericError::transform_io(UdpSocket::bind(SocketAddr::new(bind_address, 0)))?;
+
+    // Um único laço de eventos trata os acks (socket TCP), os envios (socket UDP) e os temporizadores do pacer e
+    // de retransmissão, sem threads auxiliares nem esperas ativas.
+    GenericError::transform_io(
+        poll.registry()
+            .register(stream, CONTROL, Interest::READABLE),
+    )?;
+    GenericError::transform_io(
+        poll.registry()
+            .register(&mut socket, DATA, Interest::WRITABLE),
+    )?;
+
+    let transmit = |data: &[u8]| socket.send_to(data, data_address).map(|_bytes_sent| ());
+
+    let mut events = Events::with_capacity(16);
+    let mut received_bytes: Vec<u8> = Vec::new();
+    let mut socket_writable = true;
+
+    loop {
+        // Os pedidos de `TransferHandle` acordam o poll pelo waker e são atendidos aqui.
+        if requests.aborted.load(Ordering::SeqCst) {
+            return Err(aborted_error());
+        }
+        let paused = requests.paused.load(Ordering::SeqCst);
+        if paused != sender.is_paused() {
+            let message = if paused {
+                info!("Envio pausado");
+                sender.pause();
+                Message::Pause
+            } else {
+                info!("Envio retomado");
+                sender.resume();
+                Message::Resume
+            };
+            GenericError::transform_io(stream.write_all(&message.encode()))?;
+        }
+
+        if socket_writable && !sender.is_complete() {
+            socket_writable = check_send_result(sender.send_ready_chunks(transmit))?;
+        }
+
+        // Enquanto o socket UDP não puder ser escrito, os prazos do pacer e da retransmissão não têm efeito, então
+        // só é preciso esperar pelos eventos dos sockets.
+        let deadline = if socket_writable {
+            sender.next_deadline()
+        } else {
+            None
+        };
+        let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if let Err(e) = poll.poll(&mut events, timeout) {
+            if e.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(GenericError::IO(e));
+        }
+
+        for event in events.iter() {
+            match event.token() {
+                CONTROL => {
+                    let connection_closed =
+                        GenericError::transform_io(read_available(stream, &mut received_bytes))?;
+
+                    while let Some((message, length)) =
+                        GenericError::transform_logic(parse_message(&received_bytes))?
+                    {
+                        received_bytes.drain(..length);
+                        match message {
+                            Message::Ack(ack) => {
+                                let writable = check_send_result(sender.on_ack(ack, transmit))?;
+                                socket_writable = socket_writable && writable;
+                            }
+                            Message::End => {
+                                info!("Arquivo enviado com sucesso.");
+                                return Ok(());
+                            }
+                            Message::Error(code) => return Err(GenericError::Protocol(code)),
+                            _ => {}
+                        }
+                    }
+
+                    if connection_closed {
+                        return if sender.is_complete() {
+                            info!("Arquivo enviado com sucesso.");
+                            Ok(())
+                        } else {
+                            Err(GenericError::IO(Error::new(
+                                ErrorKind::ConnectionAborted,
+                                "Conexão fechada",
+                            )))
+                        };
+                    }
+                }
+                DATA => socket_writable = true,
+                _ => {}
+            }
+        }
+
+        if socket_writable {
+            socket_writable = check_send_result(sender.on_timer(transmit))?;
+        }
+    }
+}
+
+/// Recebe uma resposta do servidor no socket bloqueante, tratando o fim do prazo de leitura como timeout.
+fn receive_reply(stream: &mut TcpStream) -> Result<Message, GenericError> {
+    match receive_message(stream) {
+        Err(GenericError::IO(e)) if e.kind() == ErrorKind::WouldBlock => Err(GenericError::IO(
+            Error::new(ErrorKind::TimedOut, "O servidor não respondeu a tempo"),
+        )),
+        result => result,
+    }
+}
+
+/// Lê todos os bytes disponíveis no socket TCP não bloqueante. Retorna se a conexão foi fechada pelo servidor.
+fn read_available(
+    stream: &mut mio::net::TcpStream,
+    received_bytes: &mut Vec<u8>,
+) -> Result<bool, Error> {
+    let mut buffer = [0; 1024];
+
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => return Ok(true),
+            Ok(bytes_read) => received_bytes.extend_from_slice(&buffer[..bytes_read]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Retorna se o socket UDP ainda aceita datagramas, ou se é necessário esperar que ele volte a ser gravável.
+fn check_send_result(result: Result<(), Error>) -> Result<bool, GenericError> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(GenericError::IO(e)),
+    }
+}
+
+fn aborted_error() -> GenericError {
+    GenericError::IO(Error::new(
+        ErrorKind::Interrupted,
+        "Transferência cancelada",
+    ))
+}
+
+fn logic_error(msg: &str) -> GenericError {
+    GenericError::Logic(MessageCreationError::new(msg))
+}
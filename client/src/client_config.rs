@@ -1,83 +1,112 @@
-use std::env;
-use std::ffi::OsStr;
 use std::net::IpAddr;
-use std::path::Path;
-use std::str;
+use std::path::PathBuf;
+use std::time::Duration;
 
+use clap::{ArgAction, Parser};
+
+use cliente::Filename;
+use common::congestion_control::CongestionAlgorithm;
+use common::logging::LogFormat;
+use common::{parse_duration, CHUNK_SIZE, MAX_CHUNK_SIZE};
+
+/// Envia arquivos para o servidor, ou baixa-os com --get, numa única sessão: o controle da transferência é feito via
+/// TCP e o conteúdo é transferido em blocos via UDP.
+///
+/// Durante o envio, SIGUSR1 alterna entre pausar e retomar a transferência, e SIGINT (Ctrl-C) a cancela.
+#[derive(Parser)]
+#[command(
+    name = "cliente",
+    version,
+    after_help = "Códigos de saída: 0 em caso de sucesso, 1 se a transferência falhar ou for cancelada e 2 para argumentos inválidos."
+)]
 pub struct ClientConfig {
+    /// Endereço IP do servidor.
     pub ip: IpAddr,
+    /// Porta TCP do servidor.
     pub port: u16,
-    pub filename: Filename,
+    /// Arquivos a enviar, um por vez: no máximo 15 caracteres, com uma única extensão de até 3 caracteres.
+    #[arg(value_name = "FILENAME", value_parser = parse_filename, required_unless_present = "dir")]
+    pub filenames: Vec<Filename>,
+    /// Envia recursivamente os arquivos do diretório, que o servidor recria no seu diretório de saída, em vez de
+    /// FILENAME.
+    #[arg(long, value_name = "DIRETÓRIO", conflicts_with_all = ["filenames", "get"])]
+    pub dir: Option<PathBuf>,
+    /// Baixa os arquivos FILENAME do servidor, salvando-os no diretório atual, em vez de enviá-los. Só os arquivos na
+    /// raiz do diretório de saída do servidor podem ser baixados, e não os enviados com --dir.
+    #[arg(long)]
+    pub get: bool,
+    /// Algoritmo de controle de congestionamento (newreno, cubic ou ledbat).
+    #[arg(long = "cc", value_name = "ALGORITMO", default_value = "newreno")]
+    pub congestion_algorithm: CongestionAlgorithm,
+    /// Taxa máxima de envio, em bits por segundo, com sufixo opcional K, M ou G (exemplo: 50M).
+    #[arg(long, value_name = "TAXA", value_parser = parse_rate)]
+    pub max_rate: Option<f64>,
+    /// Limite de blocos em trânsito, além da janela anunciada pelo servidor.
+    #[arg(long, value_name = "BLOCOS", value_parser = clap::value_parser!(u16).range(1..))]
+    pub window: Option<u16>,
+    /// Tempo máximo sem resposta do servidor, em segundos ou com sufixo ms, s ou m (exemplo: 500ms).
+    #[arg(long, value_name = "DURAÇÃO", value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+    /// Tamanho, em bytes, do conteúdo de cada datagrama de dados.
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = CHUNK_SIZE as u16,
+        value_parser = clap::value_parser!(u16).range(1..=MAX_CHUNK_SIZE as i64)
+    )]
+    pub chunk_size: u16,
+    /// Registra mais detalhes: -v para o nível debug e -vv para o nível trace.
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count)]
+    pub verbosity: u8,
+    /// Formato das linhas de log (text ou json).
+    #[arg(long, value_name = "FORMATO", default_value = "text")]
+    pub log_format: LogFormat,
+    /// Imprime as estatísticas da transferência em JSON, em vez do resumo em texto.
+    #[arg(long)]
+    pub stats_json: bool,
 }
 
-pub struct Filename {
-    pub filename: String,
+fn parse_filename(filename: &str) -> Result<Filename, &'static str> {
+    Filename::new(Some(filename.to_string()))
 }
 
-impl Filename {
-    pub fn new(filename: Option<String>) -> Result<Filename, &'static str> {
-        let filename = match filename {
-            Some(filename) => filename,
-            None => return Err("Nome do arquivo não especificado"),
-        };
-
-        if filename.len() > 15 {
-            return Err("Nome não permitido");
-        }
-        if !filename.contains(".") {
-            return Err("Nome não permitido");
-        }
-        if filename.matches(".").count() > 1 {
-            return Err("Nome não permitido");
-        }
+/// Menor taxa máxima aceita, em bytes por segundo.
+const MIN_RATE: f64 = 1.0;
 
-        let extension = Path::new(&filename)
-            .extension()
-            .and_then(OsStr::to_str)
-            .unwrap();
-        if extension.len() > 3 {
-            return Err("Nome não permitido");
-        }
+/// Interpreta uma taxa em bits por segundo, com sufixo opcional K, M ou G (potências de 1000; por exemplo, "50M" são
+/// 50 Mbit/s), e retorna o valor em bytes por segundo. A taxa deve ser de ao menos 8 bits por segundo.
+fn parse_rate(rate: &str) -> Result<f64, &'static str> {
+    let (number, multiplier) = match rate.chars().last() {
+        Some('k') | Some('K') => (&rate[..rate.len() - 1], 1e3),
+        Some('m') | Some('M') => (&rate[..rate.len() - 1], 1e6),
+        Some('g') | Some('G') => (&rate[..rate.len() - 1], 1e9),
+        _ => (rate, 1.0),
+    };
 
-        if !filename.chars().all(|ch| ch.is_ascii()) {
-            return Err("Nome não permitido");
+    match number.parse::<f64>().map(|value| value * multiplier / 8.0) {
+        Ok(bytes_per_second) if bytes_per_second >= MIN_RATE && bytes_per_second.is_finite() => {
+            Ok(bytes_per_second)
         }
-
-        return Ok(Filename { filename });
+        _ => Err("Taxa máxima inválida: o mínimo é 8 bits por segundo (exemplos: 800K, 50M, 1G)"),
     }
 }
 
-impl ClientConfig {
-    pub fn new(mut args: env::Args) -> Result<ClientConfig, &'static str> {
-        args.next();
+#[cfg(test)]
+mod tests {
+    use super::parse_rate;
 
-        let ip = match args.next() {
-            Some(ip) => ip,
-            None => return Err("IP do servidor não especificado."),
-        };
-
-        let port = match args.next() {
-            Some(port) => port,
-            None => return Err("Porta do servidor não especificada."),
-        };
-
-        let port = port
-            .parse()
-            .expect("A porta deve ser um inteiro unsigned de 16 bits");
-
-        let ip = ip.parse::<IpAddr>();
-
-        let ip = match ip {
-            Ok(ip) => ip,
-            Err(_error) => return Err("Falha ao fazer o parse do endereço ip"),
-        };
-
-        let filename = Filename::new(args.next());
-        let filename = match filename {
-            Ok(filename) => filename,
-            Err(msg) => return Err(msg),
-        };
+    #[test]
+    fn parses_rates_in_bits_per_second() {
+        assert_eq!(parse_rate("800"), Ok(100.0));
+        assert_eq!(parse_rate("50M"), Ok(6_250_000.0));
+        assert_eq!(parse_rate("1g"), Ok(125_000_000.0));
+        assert_eq!(parse_rate("8"), Ok(1.0));
+    }
 
-        Ok(ClientConfig { ip, port, filename })
+    #[test]
+    fn rejects_rates_below_one_byte_per_second() {
+        for rate in ["0", "7", "1e-300", "-5M", "NaN", "inf", "1e308G", "", "M"] {
+            assert!(parse_rate(rate).is_err(), "{:?}", rate);
+        }
     }
 }
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tracing::debug;
+
+use common::{GenericError, MessageCreationError, TransferStats, MAX_PATH_LENGTH};
+
+/// Um arquivo encontrado ao percorrer o diretório enviado por `Client::send_directory`.
+pub(crate) struct DirectoryEntry {
+    pub path: PathBuf,
+    /// Caminho relativo ao diretório enviado, com os diretórios separados por "/", como é anunciado ao servidor.
+    pub relative_path: String,
+}
+
+/// Lista recursivamente os arquivos de `directory`, em ordem alfabética. Links simbólicos e arquivos especiais são
+/// ignorados, e os diretórios vazios não aparecem na lista.
+pub(crate) fn walk_directory(directory: &Path) -> Result<Vec<DirectoryEntry>, GenericError> {
+    let mut entries = Vec::new();
+    walk(directory, "", &mut entries)?;
+    Ok(entries)
+}
+
+fn walk(
+    directory: &Path,
+    prefix: &str,
+    entries: &mut Vec<DirectoryEntry>,
+) -> Result<(), GenericError> {
+    let mut children = GenericError::transform_io(
+        fs::read_dir(directory).and_then(|children| children.collect::<Result<Vec<_>, _>>()),
+    )?;
+    children.sort_by_key(|child| child.file_name());
+
+    for child in children {
+        let name = child.file_name().into_string().map_err(|name| {
+            logic_error(&format!(
+                "Nome de arquivo inválido: {}",
+                name.to_string_lossy()
+            ))
+        })?;
+        let relative_path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+
+        let file_type = GenericError::transform_io(child.file_type())?;
+        if file_type.is_dir() {
+            walk(&child.path(), &relative_path, entries)?;
+        } else if file_type.is_file() {
+            if relative_path.len() > MAX_PATH_LENGTH {
+                return Err(logic_error(&format!(
+                    "Caminho com mais de {} bytes: {}",
+                    MAX_PATH_LENGTH, relative_path
+                )));
+            }
+            entries.push(DirectoryEntry {
+                path: child.path(),
+                relative_path,
+            });
+        } else {
+            debug!(path = %child.path().display(), "Ignorando o que não é arquivo nem diretório");
+        }
+    }
+
+    Ok(())
+}
+
+/// Resumo do envio de um diretório, retornado por `Client::send_directory`.
+#[derive(Clone, Debug, Default)]
+pub struct DirectorySummary {
+    /// Arquivos enviados e confirmados pelo servidor.
+    pub files: usize,
+    /// Soma dos tamanhos dos arquivos enviados.
+    pub bytes: u64,
+    pub retransmissions: u64,
+    pub elapsed: Duration,
+}
+
+impl DirectorySummary {
+    /// Contabiliza um arquivo enviado.
+    pub(crate) fn add(&mut self, stats: &TransferStats) {
+        self.files += 1;
+        self.bytes += stats.file_size;
+        self.retransmissions += stats.retransmissions;
+    }
+
+    /// Bytes dos arquivos transferidos por segundo, considerando todo o envio.
+    pub fn goodput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.bytes as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Representação em JSON, numa única linha, no formato de `TransferStats::to_json`.
+    pub fn to_json(&self) -> String {
+        format!(
+            concat!(
+                "{{\"files\":{},\"bytes\":{},\"retransmissions\":{},\"elapsed_ms\":{:.3},",
+                "\"goodput_bytes_per_sec\":{:.0}}}"
+            ),
+            self.files,
+            self.bytes,
+            self.retransmissions,
+            self.elapsed.as_secs_f64() * 1000.0,
+            self.goodput()
+        )
+    }
+}
+
+impl fmt::Display for DirectorySummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Diretório enviado:")?;
+        writeln!(f, "  arquivos:             {}", self.files)?;
+        writeln!(f, "  bytes:                {}", self.bytes)?;
+        writeln!(
+            f,
+            "  tempo:                {:.3} s",
+            self.elapsed.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "  goodput:              {:.1} kB/s",
+            self.goodput() / 1000.0
+        )?;
+        writeln!(f, "  retransmissões:       {}", self.retransmissions)
+    }
+}
+
+fn logic_error(msg: &str) -> GenericError {
+    GenericError::Logic(MessageCreationError::new(msg))
+}
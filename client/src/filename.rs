@@ -0,0 +1,72 @@
+use std::ffi::OsStr;
+use std::path::Path;
+
+/// Nome do arquivo como é enviado ao servidor: no máximo 15 caracteres ASCII, com uma única extensão de até 3
+/// caracteres.
+#[derive(Clone, Debug)]
+pub struct Filename {
+    pub filename: String,
+}
+
+impl Filename {
+    pub fn new(filename: Option<String>) -> Result<Filename, &'static str> {
+        let filename = match filename {
+            Some(filename) => filename,
+            None => return Err("Nome do arquivo não especificado"),
+        };
+
+        if filename.len() > 15 {
+            return Err("Nome não permitido");
+        }
+        if !filename.contains(".") {
+            return Err("Nome não permitido");
+        }
+        if filename.matches(".").count() > 1 {
+            return Err("Nome não permitido");
+        }
+
+        let extension = match Path::new(&filename).extension().and_then(OsStr::to_str) {
+            Some(extension) => extension,
+            None => return Err("Nome não permitido"),
+        };
+        if extension.len() > 3 {
+            return Err("Nome não permitido");
+        }
+
+        if !filename.is_ascii() {
+            return Err("Nome não permitido");
+        }
+
+        Ok(Filename { filename })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filename;
+
+    fn accepts(name: &str) -> bool {
+        Filename::new(Some(name.to_string())).is_ok()
+    }
+
+    #[test]
+    fn accepts_name_with_short_extension() {
+        assert!(accepts("arquivo.txt"));
+        assert!(accepts("a.b"));
+    }
+
+    #[test]
+    fn rejects_name_without_extension() {
+        assert!(!accepts(".abc"));
+        assert!(!accepts("arquivo"));
+    }
+
+    #[test]
+    fn rejects_malformed_names() {
+        assert!(!accepts("nome_muito_longo.txt"));
+        assert!(!accepts("a.tar.gz"));
+        assert!(!accepts("arquivo.text"));
+        assert!(!accepts("ação.txt"));
+        assert!(Filename::new(None).is_err());
+    }
+}
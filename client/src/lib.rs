@@ -0,0 +1,16 @@
+//! Cliente do protocolo de transferência de arquivos: handshake via TCP e envio dos blocos via UDP.
+
+mod client;
+pub use client::{Client, TransferHandle};
+
+mod directory;
+pub use directory::DirectorySummary;
+
+mod filename;
+pub use filename::Filename;
+
+mod progress_bar;
+pub use progress_bar::ProgressBar;
+
+pub use common::congestion_control::CongestionAlgorithm;
+pub use common::{GenericError, SendOptions, TransferInfo, TransferObserver, TransferStats};
@@ -0,0 +1,146 @@
+use std::io::{stderr, Write};
+use std::time::{Duration, Instant};
+
+use common::{GenericError, TransferInfo, TransferObserver};
+
+/// Largura da barra, em caracteres.
+const BAR_WIDTH: usize = 30;
+
+/// Intervalo mínimo entre duas atualizações da barra, para não gastar mais tempo escrevendo no terminal do que
+/// enviando o arquivo.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Observador que mostra, na saída de erro, uma barra de progresso com a taxa de envio e o tempo restante estimado.
+pub struct ProgressBar {
+    filename: String,
+    file_size: u64,
+    acked_bytes: u64,
+    retransmissions: u64,
+    started_at: Instant,
+    last_drawn_at: Option<Instant>,
+}
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar {
+            filename: String::new(),
+            file_size: 0,
+            acked_bytes: 0,
+            retransmissions: 0,
+            started_at: Instant::now(),
+            last_drawn_at: None,
+        }
+    }
+
+    fn draw(&mut self, force: bool) {
+        let now = Instant::now();
+        let recently_drawn = self
+            .last_drawn_at
+            .is_some_and(|drawn_at| now - drawn_at < REDRAW_INTERVAL);
+        if recently_drawn && !force {
+            return;
+        }
+        self.last_drawn_at = Some(now);
+
+        let fraction = if self.file_size == 0 {
+            1.0
+        } else {
+            self.acked_bytes as f64 / self.file_size as f64
+        };
+        let filled = (fraction * BAR_WIDTH as f64) as usize;
+
+        let elapsed = (now - self.started_at).as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            self.acked_bytes as f64 / elapsed
+        } else {
+            0.0
+        };
+        let remaining = (self.file_size - self.acked_bytes) as f64 / rate;
+        let eta = match Duration::try_from_secs_f64(remaining) {
+            Ok(remaining) if rate > 0.0 => format_duration(remaining),
+            _ => String::from("--:--"),
+        };
+
+        let mut output = stderr();
+        let _ = write!(
+            output,
+            "\r{} [{}{}] {:>3.0}% {}/s ETA {} ({} retransmissões)",
+            self.filename,
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            fraction * 100.0,
+            format_bytes(rate),
+            eta,
+            self.retransmissions
+        );
+        let _ = output.flush();
+    }
+}
+
+impl Default for ProgressBar {
+    fn default() -> ProgressBar {
+        ProgressBar::new()
+    }
+}
+
+impl TransferObserver for ProgressBar {
+    fn on_handshake(&mut self, transfer: &TransferInfo) {
+        self.filename = transfer.filename.clone();
+        self.file_size = transfer.file_size;
+        self.acked_bytes = 0;
+        self.retransmissions = 0;
+        self.started_at = Instant::now();
+        self.last_drawn_at = None;
+        self.draw(true);
+    }
+
+    fn on_chunk_acked(&mut self, _chunk: u32, acked_bytes: u64) {
+        self.acked_bytes = acked_bytes;
+        self.draw(false);
+    }
+
+    fn on_retransmit(&mut self, _chunk: u32) {
+        self.retransmissions += 1;
+    }
+
+    fn on_complete(&mut self) {
+        self.acked_bytes = self.file_size;
+        self.draw(true);
+        eprintln!();
+    }
+
+    fn on_error(&mut self, _error: &GenericError) {
+        if self.last_drawn_at.is_some() {
+            eprintln!();
+        }
+    }
+}
+
+/// Formata uma quantidade de bytes com o prefixo decimal adequado (por exemplo, "1.5 MB").
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "kB", "MB", "GB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Formata uma duração como "mm:ss", ou "hh:mm:ss" a partir de uma hora.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
@@ -0,0 +1,41 @@
+//! Envia um arquivo usando a API assíncrona.
+//!
+//! Execução: `cargo run -p common --features async --example async_send_file -- <ip:porta> <arquivo>`
+
+use std::env;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process;
+
+use common::async_network_utils::send_file;
+use common::SendOptions;
+
+#[tokio::main]
+async fn main() {
+    let mut args = env::args().skip(1);
+    let (address, path) = match (args.next(), args.next()) {
+        (Some(address), Some(path)) => (address, path),
+        _ => {
+            eprintln!("Uso: async_send_file <ip:porta> <arquivo>");
+            process::exit(1);
+        }
+    };
+
+    let address: SocketAddr = address.parse().unwrap_or_else(|_e| {
+        eprintln!("Endereço inválido: {}", address);
+        process::exit(1);
+    });
+    let file_contents = tokio::fs::read(&path).await.unwrap_or_else(|e| {
+        eprintln!("Falha ao abrir o arquivo: {}", e);
+        process::exit(1);
+    });
+    let filename = Path::new(&path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&path);
+
+    match send_file(address, filename, file_contents, &SendOptions::default()).await {
+        Ok(stats) => print!("Arquivo enviado com sucesso.\n{}", stats),
+        Err(e) => eprintln!("{}", e),
+    }
+}
@@ -0,0 +1,177 @@
+//! Equivalentes assíncronos (tokio) de `receive_message` e `send_message`, e o envio completo de um arquivo, usando
+//! o mesmo formato de mensagens e a mesma janela deslizante do cliente síncrono.
+
+use std::future;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Instant;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::{
+    parse_message, FileData, GenericError, Message, MessageCreationError, SendOptions, Sender,
+    TransferStats, MAX_DATAGRAM_SIZE,
+};
+
+/// Recebe uma mensagem do socket TCP, e transforma-a numa instância de Message, ou retorna o erro caso algum problema
+/// aconteça (erro de I/O ou lógica).
+pub async fn receive_message(stream: &mut TcpStream) -> Result<Message, GenericError> {
+    let mut buffer = [0; 1024];
+
+    read_exact(stream, &mut buffer[..2]).await?;
+    let header_length = Message::header_length(buffer[1]);
+    read_exact(stream, &mut buffer[2..header_length]).await?;
+
+    let message_length =
+        GenericError::transform_logic(Message::length_for_header(&buffer[..header_length]))?;
+    read_exact(stream, &mut buffer[header_length..message_length]).await?;
+
+    GenericError::transform_logic(Message::new(&buffer, message_length))
+}
+
+/// Envia um array de bytes para o socket TCP, e retorna quantos bytes foram enviados, ou o erro associado.
+pub async fn send_message(stream: &mut TcpStream, data: &[u8]) -> Result<usize, Error> {
+    stream.write_all(data).await?;
+
+    Ok(data.len())
+}
+
+/// Recebe um datagrama do socket UDP e transforma-o numa instância de Message, junto ao endereço de origem.
+pub async fn receive_datagram(socket: &UdpSocket) -> Result<(Message, SocketAddr), GenericError> {
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+
+    let (bytes_read, source) = GenericError::transform_io(socket.recv_from(&mut buffer).await)?;
+    let message = GenericError::transform_logic(Message::new(&buffer, bytes_read))?;
+
+    Ok((message, source))
+}
+
+/// Envia um arquivo para o servidor em `address`: faz o handshake via TCP, transfere os blocos via UDP e termina
+/// quando o servidor confirma o recebimento de todos eles. Retorna as estatísticas do envio.
+pub async fn send_file(
+    address: SocketAddr,
+    filename: &str,
+    file_contents: Vec<u8>,
+    options: &SendOptions,
+) -> Result<TransferStats, GenericError> {
+    options.validate().map_err(|e| logic_error(&e))?;
+    let mut stream = GenericError::transform_io(TcpStream::connect(address).await)?;
+
+    GenericError::transform_io(send_message(&mut stream, &Message::Hello.encode()).await)?;
+
+    let connection = match receive_message(&mut stream).await? {
+        Message::Connection(connection) => connection,
+        Message::Error(code) => return Err(GenericError::Protocol(code)),
+        _ => return Err(logic_error("Não foi possível obter a porta UDP")),
+    };
+
+    let info_file = Message::InfoFile(FileData {
+        filename: filename.to_string(),
+        file_size: file_contents.len() as u64,
+        chunk_size: options.chunk_size as u16,
+    });
+    GenericError::transform_io(send_message(&mut stream, &info_file.encode()).await)?;
+
+    match receive_message(&mut stream).await? {
+        Message::Ok => {}
+        Message::Error(code) => return Err(GenericError::Protocol(code)),
+        _ => return Err(logic_error("Tipo de mensagem inesperado")),
+    }
+
+    let bind_address: IpAddr = match address.ip() {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket =
+        GenericError::transform_io(UdpSocket::bind(SocketAddr::new(bind_address, 0)).await)?;
+    let destination = SocketAddr::new(address.ip(), connection.port as u16);
+    let transmit = |data: &[u8]| socket.try_send_to(data, destination).map(|_bytes_sent| ());
+
+    let mut sender = Sender::new(file_contents, &connection, options);
+    let mut received_bytes: Vec<u8> = Vec::new();
+    let mut buffer = [0; 1024];
+    let mut socket_writable = true;
+
+    loop {
+        if socket_writable && !sender.is_complete() {
+            socket_writable = check_send_result(sender.send_ready_chunks(transmit))?;
+        }
+
+        // Enquanto o socket UDP não puder ser escrito, os prazos do pacer e da retransmissão não têm efeito.
+        let deadline = if socket_writable {
+            sender.next_deadline()
+        } else {
+            None
+        };
+
+        tokio::select! {
+            bytes_read = stream.read(&mut buffer) => {
+                let bytes_read = GenericError::transform_io(bytes_read)?;
+                if bytes_read == 0 {
+                    return if sender.is_complete() {
+                        Ok(sender.stats())
+                    } else {
+                        Err(GenericError::IO(Error::new(ErrorKind::ConnectionAborted, "Conexão fechada")))
+                    };
+                }
+                received_bytes.extend_from_slice(&buffer[..bytes_read]);
+
+                while let Some((message, length)) =
+                    GenericError::transform_logic(parse_message(&received_bytes))?
+                {
+                    received_bytes.drain(..length);
+                    match message {
+                        Message::Ack(ack) => {
+                            let writable = check_send_result(sender.on_ack(ack, transmit))?;
+                            socket_writable = socket_writable && writable;
+                        }
+                        Message::End => return Ok(sender.stats()),
+                        Message::Error(code) => return Err(GenericError::Protocol(code)),
+                        _ => {}
+                    }
+                }
+            }
+            _ = sleep_until(deadline) => {}
+            writable = socket.writable(), if !socket_writable => {
+                GenericError::transform_io(writable)?;
+                socket_writable = true;
+            }
+        }
+
+        if socket_writable {
+            socket_writable = check_send_result(sender.on_timer(transmit))?;
+        }
+    }
+}
+
+async fn read_exact(stream: &mut TcpStream, buffer: &mut [u8]) -> Result<(), GenericError> {
+    stream.read_exact(buffer).await.map(|_bytes_read| ()).map_err(|err| {
+        if err.kind() == ErrorKind::UnexpectedEof {
+            GenericError::IO(Error::new(ErrorKind::ConnectionAborted, "Conexão fechada"))
+        } else {
+            GenericError::IO(err)
+        }
+    })
+}
+
+/// Espera até `deadline`, ou para sempre, caso não haja prazo.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => future::pending().await,
+    }
+}
+
+/// Retorna se o socket UDP ainda aceita datagramas, ou se é necessário esperar que ele volte a ser gravável.
+fn check_send_result(result: Result<(), Error>) -> Result<bool, GenericError> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(GenericError::IO(e)),
+    }
+}
+
+fn logic_error(msg: &str) -> GenericError {
+    GenericError::Logic(MessageCreationError::new(msg))
+}
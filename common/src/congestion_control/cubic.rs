@@ -0,0 +1,221 @@
+use std::time::Instant;
+
+use super::{AckEvent, CongestionController, RttTracker, INITIAL_CWND, MIN_CWND};
+
+/// Fator de redução multiplicativa do CUBIC.
+const BETA: f64 = 0.7;
+/// Constante de escala da função cúbica.
+const C: f64 = 0.4;
+
+/// CUBIC (RFC 8312): após uma perda, a janela cresce segundo uma função cúbica do tempo decorrido, centrada na janela
+/// em que a perda aconteceu, o que torna o crescimento independente do RTT.
+pub struct Cubic {
+    cwnd: f64,
+    ssthresh: f64,
+    /// Janela no momento da última redução.
+    w_max: f64,
+    /// Início da época atual de congestion avoidance.
+    epoch_start: Option<Instant>,
+    /// Tempo, em segundos, que a função cúbica leva para voltar a `w_max`.
+    k: f64,
+    /// Estimativa da janela que o Reno teria no mesmo período (região "TCP-friendly").
+    w_est: f64,
+    recover: Option<u32>,
+    rtt: RttTracker,
+}
+
+impl Cubic {
+    pub fn new() -> Cubic {
+        Cubic {
+            cwnd: INITIAL_CWND,
+            ssthresh: f64::INFINITY,
+            w_max: 0.0,
+            epoch_start: None,
+            k: 0.0,
+            w_est: 0.0,
+            recover: None,
+            rtt: RttTracker::new(),
+        }
+    }
+
+    fn reduce(&mut self) {
+        self.epoch_start = None;
+        self.w_max = self.cwnd;
+        self.ssthresh = (self.cwnd * BETA).max(MIN_CWND);
+    }
+}
+
+impl Default for Cubic {
+    fn default() -> Cubic {
+        Cubic::new()
+    }
+}
+
+impl CongestionController for Cubic {
+    fn on_ack(&mut self, event: &AckEvent) {
+        if let Some(rtt) = event.rtt {
+            self.rtt.on_sample(rtt);
+        }
+
+        for _ in 0..event.acked_chunks {
+            if self.cwnd < self.ssthresh {
+                self.cwnd += 1.0;
+                continue;
+            }
+
+            let epoch_start = match self.epoch_start {
+                Some(epoch_start) => epoch_start,
+                None => {
+                    self.epoch_start = Some(event.now);
+                    self.w_est = self.cwnd;
+                    self.k = if self.w_max > self.cwnd {
+                        ((self.w_max - self.cwnd) / C).cbrt()
+                    } else {
+                        self.w_max = self.cwnd;
+                        0.0
+                    };
+                    event.now
+                }
+            };
+
+            let rtt = self.rtt.srtt.map(|srtt| srtt.as_secs_f64()).unwrap_or(0.0);
+            let t = (event.now - epoch_start).as_secs_f64() + rtt;
+            let target = C * (t - self.k).powi(3) + self.w_max;
+
+            self.w_est += 3.0 * (1.0 - BETA) / (1.0 + BETA) / self.cwnd;
+
+            if target > self.cwnd {
+                self.cwnd += (target - self.cwnd) / self.cwnd;
+            } else {
+                self.cwnd += 0.01 / self.cwnd;
+            }
+            if self.w_est > self.cwnd {
+                self.cwnd = self.w_est;
+            }
+        }
+    }
+
+    fn on_loss(&mut self, _now: Instant, lost_chunk: u32, highest_sent: u32) {
+        if let Some(recover) = self.recover {
+            if lost_chunk <= recover {
+                return;
+            }
+        }
+
+        self.recover = Some(highest_sent);
+        self.reduce();
+        self.cwnd = self.ssthresh;
+    }
+
+    fn on_timeout(&mut self, _now: Instant) {
+        self.reduce();
+        self.cwnd = 1.0;
+        self.recover = None;
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd as u32
+    }
+
+    fn pacing_rate(&self) -> Option<f64> {
+        let gain = if self.cwnd < self.ssthresh { 2.0 } else { 1.25 };
+        self.rtt.window_rate(self.cwnd).map(|rate| rate * gain)
+    }
+
+    fn name(&self) -> &'static str {
+        "cubic"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Cubic, BETA};
+    use crate::congestion_control::{AckEvent, CongestionController};
+
+    const RTT: Duration = Duration::from_millis(100);
+
+    /// Confirma uma janela inteira de blocos por RTT, durante `duration`, a partir de `now`.
+    fn ack_for(controller: &mut Cubic, now: &mut Instant, duration: Duration) {
+        let end = *now + duration;
+        while *now < end {
+            let window = controller.cwnd();
+            for i in 0..window {
+                controller.on_ack(&AckEvent {
+                    now: *now + RTT.mul_f64(i as f64 / window as f64),
+                    acked_chunks: 1,
+                    rtt: Some(RTT),
+                });
+            }
+            *now += RTT;
+        }
+    }
+
+    #[test]
+    fn reduces_by_beta_on_loss() {
+        let mut controller = Cubic::new();
+        for _ in 0..98 {
+            controller.on_ack(&AckEvent {
+                now: Instant::now(),
+                acked_chunks: 1,
+                rtt: Some(RTT),
+            });
+        }
+        assert_eq!(controller.cwnd(), 100);
+
+        controller.on_loss(Instant::now(), 50, 120);
+        assert_eq!(controller.cwnd(), (100.0 * BETA) as u32);
+        controller.on_loss(Instant::now(), 60, 130);
+        assert_eq!(controller.cwnd(), (100.0 * BETA) as u32);
+    }
+
+    #[test]
+    fn climbs_back_toward_w_max() {
+        let mut controller = Cubic::new();
+        let mut now = Instant::now();
+        for _ in 0..98 {
+            controller.on_ack(&AckEvent {
+                now,
+                acked_chunks: 1,
+                rtt: Some(RTT),
+            });
+        }
+        controller.on_loss(now, 50, 120);
+        let w_max = 100;
+        let reduced = controller.cwnd();
+
+        // A função cúbica volta a `w_max` em K = cbrt(w_max * (1 - BETA) / C) segundos, cerca de 4,2s.
+        ack_for(&mut controller, &mut now, Duration::from_secs(2));
+        let halfway = controller.cwnd();
+        assert!(
+            reduced < halfway && halfway < w_max,
+            "{} {} {}",
+            reduced,
+            halfway,
+            w_max
+        );
+
+        ack_for(&mut controller, &mut now, Duration::from_millis(2200));
+        let at_k = controller.cwnd();
+        assert!(
+            (w_max * 95 / 100..=w_max * 105 / 100).contains(&at_k),
+            "{}",
+            at_k
+        );
+
+        // Perto de `w_max` o crescimento é lento, e depois dele volta a acelerar, procurando uma janela maior.
+        ack_for(&mut controller, &mut now, Duration::from_secs(1));
+        let plateau_growth = controller.cwnd() - at_k;
+        ack_for(&mut controller, &mut now, Duration::from_secs(3));
+        let before_probe = controller.cwnd();
+        ack_for(&mut controller, &mut now, Duration::from_secs(1));
+        let probe_growth = controller.cwnd() - before_probe;
+        assert!(
+            probe_growth > plateau_growth,
+            "{} {}",
+            plateau_growth,
+            probe_growth
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::{Duration, Instant};
+
+use super::{AckEvent, CongestionController, RttTracker, INITIAL_CWND, MIN_CWND};
+
+/// Atraso de fila que o LEDBAT tenta manter. A RFC usa 100ms para atraso em um sentido; aqui o atraso é estimado pelo
+/// RTT, então o alvo é menor.
+const TARGET_QUEUING_DELAY: Duration = Duration::from_millis(50);
+/// Ganho aplicado à diferença entre o atraso medido e o alvo.
+const GAIN: f64 = 1.0;
+
+/// LEDBAT (RFC 6817), baseado em atraso: estima o atraso de fila como a diferença entre o RTT atual e o menor RTT
+/// observado, e ajusta a janela proporcionalmente à distância desse atraso até o alvo. Cede banda para fluxos que
+/// usam perdas como sinal de congestionamento, pois reduz a janela antes que a fila encha.
+pub struct Ledbat {
+    cwnd: f64,
+    /// Último RTT medido.
+    current_rtt: Option<Duration>,
+    recover: Option<u32>,
+    rtt: RttTracker,
+}
+
+impl Ledbat {
+    pub fn new() -> Ledbat {
+        Ledbat {
+            cwnd: INITIAL_CWND,
+            current_rtt: None,
+            recover: None,
+            rtt: RttTracker::new(),
+        }
+    }
+
+    fn queuing_delay(&self) -> Option<Duration> {
+        match (self.current_rtt, self.rtt.min_rtt) {
+            (Some(current), Some(base)) => Some(current.saturating_sub(base)),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Ledbat {
+    fn default() -> Ledbat {
+        Ledbat::new()
+    }
+}
+
+impl CongestionController for Ledbat {
+    fn on_ack(&mut self, event: &AckEvent) {
+        if let Some(rtt) = event.rtt {
+            self.rtt.on_sample(rtt);
+            self.current_rtt = Some(rtt);
+        }
+
+        let queuing_delay = match self.queuing_delay() {
+            Some(queuing_delay) => queuing_delay,
+            None => return,
+        };
+
+        let target = TARGET_QUEUING_DELAY.as_secs_f64();
+        let off_target = (target - queuing_delay.as_secs_f64()) / target;
+        let acked = event.acked_chunks as f64;
+
+        // O crescimento nunca é mais rápido que o do slow start (um bloco por bloco confirmado).
+        let increase = (GAIN * off_target * acked / self.cwnd).min(acked);
+        self.cwnd = (self.cwnd + increase).max(MIN_CWND);
+    }
+
+    fn on_loss(&mut self, _now: Instant, lost_chunk: u32, highest_sent: u32) {
+        if let Some(recover) = self.recover {
+            if lost_chunk <= recover {
+                return;
+            }
+        }
+
+        self.recover = Some(highest_sent);
+        self.cwnd = (self.cwnd / 2.0).max(MIN_CWND);
+    }
+
+    fn on_timeout(&mut self, _now: Instant) {
+        self.cwnd = 1.0;
+        self.recover = None;
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd as u32
+    }
+
+    fn pacing_rate(&self) -> Option<f64> {
+        self.rtt.window_rate(self.cwnd)
+    }
+
+    fn name(&self) -> &'static str {
+        "ledbat"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Ledbat, TARGET_QUEUING_DELAY};
+    use crate::congestion_control::{AckEvent, CongestionController};
+
+    const BASE_RTT: Duration = Duration::from_millis(40);
+
+    fn ack(controller: &mut Ledbat, count: u32, rtt: Duration) {
+        for _ in 0..count {
+            controller.on_ack(&AckEvent {
+                now: Instant::now(),
+                acked_chunks: 1,
+                rtt: Some(rtt),
+            });
+        }
+    }
+
+    #[test]
+    fn grows_while_queuing_delay_is_below_target() {
+        let mut controller = Ledbat::new();
+        ack(&mut controller, 200, BASE_RTT);
+        let cwnd = controller.cwnd();
+        assert!(cwnd > 10, "{}", cwnd);
+
+        ack(&mut controller, 200, BASE_RTT + TARGET_QUEUING_DELAY / 2);
+        assert!(controller.cwnd() > cwnd);
+    }
+
+    #[test]
+    fn backs_off_when_queuing_delay_exceeds_target() {
+        let mut controller = Ledbat::new();
+        ack(&mut controller, 400, BASE_RTT);
+        let cwnd = controller.cwnd();
+
+        ack(&mut controller, 100, BASE_RTT + TARGET_QUEUING_DELAY * 3);
+        let reduced = controller.cwnd();
+        assert!(reduced < cwnd, "{} {}", reduced, cwnd);
+
+        // Com o atraso no alvo, a janela se mantém.
+        ack(&mut controller, 100, BASE_RTT + TARGET_QUEUING_DELAY);
+        assert_eq!(controller.cwnd(), reduced);
+    }
+
+    #[test]
+    fn halves_cwnd_once_per_recovery_episode() {
+        let mut controller = Ledbat::new();
+        ack(&mut controller, 400, BASE_RTT);
+        let cwnd = controller.cwnd();
+
+        controller.on_loss(Instant::now(), 10, 50);
+        controller.on_loss(Instant::now(), 20, 60);
+        let reduced = controller.cwnd();
+        assert!(
+            reduced == cwnd / 2 || reduced == cwnd / 2 + 1,
+            "{} {}",
+            cwnd,
+            reduced
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+mod cubic;
+mod ledbat;
+mod new_reno;
+#[cfg(test)]
+mod simulation;
+
+pub use cubic::Cubic;
+pub use ledbat::Ledbat;
+pub use new_reno::NewReno;
+
+/// Janela de congestionamento inicial, em blocos (RFC 5681 permite de 2 a 4 segmentos).
+const INITIAL_CWND: f64 = 2.0;
+/// Menor janela permitida após uma perda, em blocos.
+const MIN_CWND: f64 = 2.0;
+
+/// Informações de um ack que confirmou blocos novos.
+pub struct AckEvent {
+    /// Instante em que o ack foi recebido.
+    pub now: Instant,
+    /// Quantidade de blocos confirmados por este ack (que é cumulativo).
+    pub acked_chunks: u32,
+    /// RTT medido, caso o bloco confirmado não tenha sido retransmitido (algoritmo de Karn).
+    pub rtt: Option<Duration>,
+}
+
+/// Algoritmo de controle de congestionamento usado pelo remetente.
+///
+/// A janela é medida em blocos. O remetente informa os eventos de ack, perda (acks duplicados) e timeout, e consulta a
+/// janela e a taxa de envio sugerida.
+pub trait CongestionController: Send {
+    /// Novos blocos foram confirmados.
+    fn on_ack(&mut self, event: &AckEvent);
+
+    /// Perda do bloco `lost_chunk` detectada por acks duplicados, quando o maior bloco enviado era `highest_sent`.
+    fn on_loss(&mut self, now: Instant, lost_chunk: u32, highest_sent: u32);
+
+    /// O temporizador de retransmissão expirou.
+    fn on_timeout(&mut self, now: Instant);
+
+    /// Quantidade de blocos que podem estar em trânsito.
+    fn cwnd(&self) -> u32;
+
+    /// Taxa de envio sugerida, em blocos por segundo, ou None caso ainda não haja medição de RTT.
+    fn pacing_rate(&self) -> Option<f64>;
+
+    /// Nome do algoritmo, para exibição.
+    fn name(&self) -> &'static str;
+}
+
+/// Algoritmos de controle de congestionamento disponíveis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CongestionAlgorithm {
+    NewReno,
+    Cubic,
+    Ledbat,
+}
+
+impl CongestionAlgorithm {
+    pub const ALL: [CongestionAlgorithm; 3] = [
+        CongestionAlgorithm::NewReno,
+        CongestionAlgorithm::Cubic,
+        CongestionAlgorithm::Ledbat,
+    ];
+
+    /// Cria uma nova instância do controlador correspondente.
+    pub fn build(self) -> Box<dyn CongestionController> {
+        match self {
+            CongestionAlgorithm::NewReno => Box::new(NewReno::new()),
+            CongestionAlgorithm::Cubic => Box::new(Cubic::new()),
+            CongestionAlgorithm::Ledbat => Box::new(Ledbat::new()),
+        }
+    }
+}
+
+impl FromStr for CongestionAlgorithm {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<CongestionAlgorithm, &'static str> {
+        match name.to_ascii_lowercase().as_str() {
+            "newreno" | "reno" => Ok(CongestionAlgorithm::NewReno),
+            "cubic" => Ok(CongestionAlgorithm::Cubic),
+            "ledbat" => Ok(CongestionAlgorithm::Ledbat),
+            _ => Err("Algoritmo de controle de congestionamento desconhecido (use newreno, cubic ou ledbat)"),
+        }
+    }
+}
+
+/// Acompanha o RTT suavizado e o menor RTT observado, usados pelos algoritmos para calcular a taxa de envio.
+struct RttTracker {
+    srtt: Option<Duration>,
+    min_rtt: Option<Duration>,
+}
+
+impl RttTracker {
+    fn new() -> RttTracker {
+        RttTracker {
+            srtt: None,
+            min_rtt: None,
+        }
+    }
+
+    fn on_sample(&mut self, rtt: Duration) {
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+            None => rtt,
+        });
+        self.min_rtt = Some(match self.min_rtt {
+            Some(min_rtt) if min_rtt < rtt => min_rtt,
+            _ => rtt,
+        });
+    }
+
+    /// Taxa que envia uma janela inteira a cada RTT.
+    fn window_rate(&self, cwnd: f64) -> Option<f64> {
+        self.srtt
+            .filter(|srtt| !srtt.is_zero())
+            .map(|srtt| cwnd / srtt.as_secs_f64())
+    }
+}
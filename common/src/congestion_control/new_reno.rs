@@ -0,0 +1,146 @@
+use std::time::Instant;
+
+use super::{AckEvent, CongestionController, RttTracker, INITIAL_CWND, MIN_CWND};
+
+/// NewReno (RFC 6582): slow start e AIMD, reduzindo a janela no máximo uma vez por janela de dados perdida.
+pub struct NewReno {
+    cwnd: f64,
+    ssthresh: f64,
+    /// Maior bloco enviado quando a última perda foi detectada; perdas até ele pertencem à mesma recuperação.
+    recover: Option<u32>,
+    rtt: RttTracker,
+}
+
+impl NewReno {
+    pub fn new() -> NewReno {
+        NewReno {
+            cwnd: INITIAL_CWND,
+            ssthresh: f64::INFINITY,
+            recover: None,
+            rtt: RttTracker::new(),
+        }
+    }
+}
+
+impl Default for NewReno {
+    fn default() -> NewReno {
+        NewReno::new()
+    }
+}
+
+impl CongestionController for NewReno {
+    fn on_ack(&mut self, event: &AckEvent) {
+        if let Some(rtt) = event.rtt {
+            self.rtt.on_sample(rtt);
+        }
+
+        for _ in 0..event.acked_chunks {
+            if self.cwnd < self.ssthresh {
+                self.cwnd += 1.0;
+            } else {
+                self.cwnd += 1.0 / self.cwnd;
+            }
+        }
+    }
+
+    fn on_loss(&mut self, _now: Instant, lost_chunk: u32, highest_sent: u32) {
+        if let Some(recover) = self.recover {
+            if lost_chunk <= recover {
+                return;
+            }
+        }
+
+        self.recover = Some(highest_sent);
+        self.ssthresh = (self.cwnd / 2.0).max(MIN_CWND);
+        self.cwnd = self.ssthresh;
+    }
+
+    fn on_timeout(&mut self, _now: Instant) {
+        self.ssthresh = (self.cwnd / 2.0).max(MIN_CWND);
+        self.cwnd = 1.0;
+        self.recover = None;
+    }
+
+    fn cwnd(&self) -> u32 {
+        self.cwnd as u32
+    }
+
+    fn pacing_rate(&self) -> Option<f64> {
+        // Em slow start a janela dobra a cada RTT, então a taxa também é dobrada para não atrasar o crescimento.
+        let gain = if self.cwnd < self.ssthresh { 2.0 } else { 1.25 };
+        self.rtt.window_rate(self.cwnd).map(|rate| rate * gain)
+    }
+
+    fn name(&self) -> &'static str {
+        "newreno"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::NewReno;
+    use crate::congestion_control::{AckEvent, CongestionController};
+
+    fn ack(controller: &mut NewReno, acked_chunks: u32) {
+        controller.on_ack(&AckEvent {
+            now: Instant::now(),
+            acked_chunks,
+            rtt: Some(Duration::from_millis(50)),
+        });
+    }
+
+    #[test]
+    fn slow_start_grows_one_chunk_per_ack() {
+        let mut controller = NewReno::new();
+        ack(&mut controller, 18);
+        assert_eq!(controller.cwnd(), 20);
+    }
+
+    #[test]
+    fn halves_cwnd_once_per_recovery_episode() {
+        let mut controller = NewReno::new();
+        ack(&mut controller, 38);
+        assert_eq!(controller.cwnd(), 40);
+
+        controller.on_loss(Instant::now(), 10, 49);
+        assert_eq!(controller.cwnd(), 20);
+
+        // Perdas de blocos enviados antes da redução pertencem ao mesmo episódio.
+        controller.on_loss(Instant::now(), 12, 55);
+        controller.on_loss(Instant::now(), 49, 60);
+        assert_eq!(controller.cwnd(), 20);
+
+        controller.on_loss(Instant::now(), 50, 70);
+        assert_eq!(controller.cwnd(), 10);
+    }
+
+    #[test]
+    fn grows_linearly_after_recovery() {
+        let mut controller = NewReno::new();
+        ack(&mut controller, 38);
+        controller.on_loss(Instant::now(), 10, 49);
+        assert_eq!(controller.cwnd(), 20);
+
+        // Em congestion avoidance, uma janela inteira de acks aumenta a janela em um bloco.
+        ack(&mut controller, 20);
+        assert_eq!(controller.cwnd(), 20);
+        ack(&mut controller, 1);
+        assert_eq!(controller.cwnd(), 21);
+    }
+
+    #[test]
+    fn timeout_restarts_slow_start() {
+        let mut controller = NewReno::new();
+        ack(&mut controller, 38);
+        controller.on_timeout(Instant::now());
+        assert_eq!(controller.cwnd(), 1);
+
+        // O slow start vai até a metade da janela anterior ao timeout.
+        ack(&mut controller, 19);
+        assert_eq!(controller.cwnd(), 20);
+        ack(&mut controller, 1);
+        assert_eq!(controller.cwnd(), 20);
+    }
+}
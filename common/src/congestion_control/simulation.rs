@@ -0,0 +1,233 @@
+//! Simulação dos algoritmos de controle de congestionamento sobre um enlace com gargalo, fila limitada e perdas
+//! aleatórias. Cada algoritmo transfere o mesmo arquivo com a mesma semente, ou seja, sob o mesmo perfil de perdas, e
+//! a vazão obtida é comparada.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::{AckEvent, CongestionAlgorithm};
+
+/// Semente do gerador de perdas, compartilhada por todos os algoritmos.
+const SEED: u64 = 0x5eed_1234_abcd_0001;
+/// Tamanho do arquivo transferido em cada simulação, em blocos.
+const FILE_CHUNKS: u64 = 100_000;
+/// Tempo simulado a partir do qual a transferência é considerada travada.
+const MAX_SIMULATION_TIME: Duration = Duration::from_secs(3600);
+
+struct LinkProfile {
+    /// Capacidade do gargalo, em blocos por segundo.
+    bandwidth: f64,
+    base_rtt: Duration,
+    /// Quantidade de blocos que cabem na fila do gargalo.
+    queue_capacity: f64,
+    /// Probabilidade de perda aleatória de cada bloco.
+    loss_rate: f64,
+}
+
+const CLEAN: LinkProfile = LinkProfile {
+    bandwidth: 2500.0,
+    base_rtt: Duration::from_millis(50),
+    queue_capacity: 60.0,
+    loss_rate: 0.0,
+};
+
+const LOSSY: LinkProfile = LinkProfile {
+    bandwidth: 2500.0,
+    base_rtt: Duration::from_millis(50),
+    queue_capacity: 60.0,
+    loss_rate: 0.01,
+};
+
+/// Fila do gargalo maior que o atraso alvo do LEDBAT (400ms, contra 50ms).
+const DEEP_BUFFER: LinkProfile = LinkProfile {
+    bandwidth: 2500.0,
+    base_rtt: Duration::from_millis(50),
+    queue_capacity: 1000.0,
+    loss_rate: 0.0,
+};
+
+const LONG_FAT: LinkProfile = LinkProfile {
+    bandwidth: 12500.0,
+    base_rtt: Duration::from_millis(200),
+    queue_capacity: 500.0,
+    loss_rate: 0.001,
+};
+
+struct SimulationResult {
+    delivered_chunks: u64,
+    timeouts: u64,
+    elapsed: Duration,
+    /// Média, por rodada, dos blocos na fila do gargalo.
+    average_queue: f64,
+}
+
+impl SimulationResult {
+    /// Fração da capacidade do enlace usada na entrega do arquivo.
+    fn utilization(&self, link: &LinkProfile) -> f64 {
+        self.delivered_chunks as f64 / self.elapsed.as_secs_f64() / link.bandwidth
+    }
+}
+
+/// Gerador pseudoaleatório xorshift64*, para que as perdas sejam reprodutíveis.
+struct Rng(u64);
+
+impl Rng {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let value = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Simula a transferência de `FILE_CHUNKS` blocos em rodadas de um RTT: a cada rodada uma janela inteira é enviada,
+/// começando pelos blocos perdidos, o excedente acima do produto banda-atraso entra na fila (aumentando o RTT) e o que
+/// não cabe na fila é descartado. Uma rodada sem nenhum bloco entregue termina em timeout.
+fn simulate(algorithm: CongestionAlgorithm, link: &LinkProfile) -> SimulationResult {
+    let mut controller = algorithm.build();
+    let mut rng = Rng(SEED);
+    let start = Instant::now();
+    let mut now = start;
+    let mut next_sequence_number: u32 = 0;
+    let mut lost: VecDeque<u32> = VecDeque::new();
+
+    let bdp = link.bandwidth * link.base_rtt.as_secs_f64();
+    let mut delivered_chunks = 0;
+    let mut timeouts = 0;
+    let mut total_queue = 0.0;
+    let mut rounds = 0;
+
+    while delivered_chunks < FILE_CHUNKS && now - start < MAX_SIMULATION_TIME {
+        let pending = FILE_CHUNKS - delivered_chunks;
+        let window = (controller.cwnd().max(1) as u64).min(pending) as u32;
+        let queued = (window as f64 - bdp).max(0.0);
+        let congestion_drops = (queued - link.queue_capacity).max(0.0).ceil() as u32;
+        let queued = queued.min(link.queue_capacity);
+        let rtt = link.base_rtt + Duration::from_secs_f64(queued / link.bandwidth);
+
+        let round: Vec<u32> = (0..window)
+            .map(|_| {
+                lost.pop_front().unwrap_or_else(|| {
+                    next_sequence_number += 1;
+                    next_sequence_number - 1
+                })
+            })
+            .collect();
+        let highest_sent = next_sequence_number.saturating_sub(1);
+
+        let mut delivered = 0;
+        let mut lost_in_round = Vec::new();
+        for (i, &sequence_number) in round.iter().enumerate() {
+            let dropped_by_queue = i as u32 >= window - congestion_drops;
+            if dropped_by_queue || rng.next_f64() < link.loss_rate {
+                lost_in_round.push(sequence_number);
+            } else {
+                delivered += 1;
+                controller.on_ack(&AckEvent {
+                    now: now + rtt.mul_f64((i + 1) as f64 / window as f64),
+                    acked_chunks: 1,
+                    rtt: Some(rtt),
+                });
+            }
+        }
+        now += rtt;
+
+        if delivered == 0 {
+            now += (rtt * 2).max(Duration::from_millis(200));
+            controller.on_timeout(now);
+            timeouts += 1;
+        } else {
+            for &lost_chunk in &lost_in_round {
+                controller.on_loss(now, lost_chunk, highest_sent);
+            }
+        }
+        lost.extend(lost_in_round);
+
+        delivered_chunks += delivered;
+        total_queue += queued;
+        rounds += 1;
+    }
+
+    SimulationResult {
+        delivered_chunks,
+        timeouts,
+        elapsed: now - start,
+        average_queue: total_queue / rounds as f64,
+    }
+}
+
+#[test]
+fn every_controller_delivers_the_file() {
+    for link in &[CLEAN, LOSSY, DEEP_BUFFER, LONG_FAT] {
+        for &algorithm in CongestionAlgorithm::ALL.iter() {
+            let result = simulate(algorithm, link);
+            assert_eq!(result.delivered_chunks, FILE_CHUNKS, "{:?}", algorithm);
+            assert!(result.elapsed < MAX_SIMULATION_TIME, "{:?}", algorithm);
+        }
+    }
+}
+
+#[test]
+fn loss_based_controllers_fill_a_clean_link() {
+    for &algorithm in &[CongestionAlgorithm::NewReno, CongestionAlgorithm::Cubic] {
+        let result = simulate(algorithm, &CLEAN);
+        assert!(
+            result.utilization(&CLEAN) > 0.9,
+            "{:?}: {:.2}",
+            algorithm,
+            result.utilization(&CLEAN)
+        );
+        assert_eq!(result.timeouts, 0, "{:?}", algorithm);
+    }
+}
+
+#[test]
+fn cubic_outperforms_new_reno_on_a_long_fat_link() {
+    let new_reno = simulate(CongestionAlgorithm::NewReno, &LONG_FAT);
+    let cubic = simulate(CongestionAlgorithm::Cubic, &LONG_FAT);
+    assert!(
+        cubic.utilization(&LONG_FAT) > new_reno.utilization(&LONG_FAT),
+        "cubic {:.2}, newreno {:.2}",
+        cubic.utilization(&LONG_FAT),
+        new_reno.utilization(&LONG_FAT)
+    );
+}
+
+#[test]
+fn ledbat_keeps_the_bottleneck_queue_shorter() {
+    let new_reno = simulate(CongestionAlgorithm::NewReno, &DEEP_BUFFER);
+    let ledbat = simulate(CongestionAlgorithm::Ledbat, &DEEP_BUFFER);
+    assert!(
+        ledbat.average_queue < new_reno.average_queue / 2.0,
+        "ledbat {:.1}, newreno {:.1}",
+        ledbat.average_queue,
+        new_reno.average_queue
+    );
+}
+
+/// Imprime a comparação dos algoritmos em todos os perfis: `cargo test -p common print_comparison -- --ignored
+/// --nocapture`.
+#[test]
+#[ignore]
+fn print_comparison() {
+    for (name, link) in &[
+        ("limpo", CLEAN),
+        ("1% de perda", LOSSY),
+        ("fila longa", DEEP_BUFFER),
+        ("longo", LONG_FAT),
+    ] {
+        for &algorithm in CongestionAlgorithm::ALL.iter() {
+            let result = simulate(algorithm, link);
+            println!(
+                "{} {:?}: utilização {:.3}, fila {:.1}, timeouts {}, {:?}",
+                name,
+                algorithm,
+                result.utilization(link),
+                result.average_queue,
+                result.timeouts,
+                result.elapsed
+            );
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+/// Maior duração aceita: prazos maiores fariam a soma com um `Instant` estourar.
+const MAX_DURATION: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Interpreta uma duração em segundos, com sufixo opcional ms, s ou m (por exemplo, "500ms", "30" ou "2m"), no formato
+/// usado pelos argumentos de linha de comando e pelos arquivos de configuração.
+pub fn parse_duration(duration: &str) -> Result<Duration, &'static str> {
+    let duration = duration.trim();
+    let (number, unit) = if let Some(number) = duration.strip_suffix("ms") {
+        (number, 1e-3)
+    } else if let Some(number) = duration.strip_suffix('s') {
+        (number, 1.0)
+    } else if let Some(number) = duration.strip_suffix('m') {
+        (number, 60.0)
+    } else {
+        (duration, 1.0)
+    };
+
+    // Valores grandes demais, como "1e30", são rejeitados em vez de causar um pânico.
+    match number
+        .parse::<f64>()
+        .map(|value| Duration::try_from_secs_f64(value * unit))
+    {
+        Ok(Ok(duration)) if !duration.is_zero() && duration <= MAX_DURATION => Ok(duration),
+        _ => Err("Duração inválida (exemplos: 500ms, 30s, 2m)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::parse_duration;
+
+    #[test]
+    fn parses_durations_with_and_without_unit() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration(" 1.5s "), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("525600m"), Ok(super::MAX_DURATION));
+    }
+
+    #[test]
+    fn rejects_invalid_and_out_of_range_durations() {
+        for duration in [
+            "", "abc", "0", "-1s", "NaN", "inf", "1e30", "1e30m", "1.8e19", "1e-320ms",
+        ] {
+            assert!(parse_duration(duration).is_err(), "{}", duration);
+        }
+    }
+}
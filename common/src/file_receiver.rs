@@ -0,0 +1,337 @@
+use tracing::{debug, trace};
+
+/// Resultado do processamento de um bloco recebido.
+pub struct ChunkOutcome {
+    /// Acks a serem enviados ao emissor, em ordem.
+    pub acks: Vec<u32>,
+    /// Blocos que passaram a ser contíguos, prontos para escrita em disco, em ordem.
+    pub ready_to_write: Vec<Vec<u8>>,
+    /// Todos os blocos foram recebidos e o ack do último foi enviado.
+    pub finished: bool,
+    /// O bloco já havia sido recebido, ou seja, o emissor o retransmitiu.
+    pub duplicate: bool,
+    /// O bloco foi descartado por estar além da janela de recepção ou fora do arquivo.
+    pub dropped: bool,
+    /// O bloco foi descartado por não ter o tamanho esperado: `chunk_size` bytes, ou o restante do arquivo no último
+    /// bloco.
+    pub malformed: bool,
+}
+
+/// Estado do recebimento de um arquivo com janela deslizante, usado pelo servidor no envio de arquivos e pelo cliente
+/// no download. Não faz I/O: cada bloco recebido é processado por `on_chunk`, que informa quais acks devem ser
+/// enviados e quais blocos podem ser escritos.
+///
+/// A memória usada é proporcional à janela de recepção, e não ao tamanho do arquivo: só os blocos recebidos fora de
+/// ordem ficam guardados, até que os anteriores cheguem e todos sejam entregues para a escrita.
+pub struct FileReceiver {
+    expected_chunks: u64,
+    chunk_size: usize,
+    last_chunk_size: usize,
+    // Blocos recebidos e ainda não entregues, num buffer circular: o bloco `n` ocupa a posição `n % pending.len()`.
+    // Como só são aceitos blocos dentro da janela, dois blocos pendentes nunca ocupam a mesma posição.
+    pending: Vec<Option<Vec<u8>>>,
+    // Próximo bloco a ser entregue para a escrita em disco; os anteriores já foram recebidos e entregues em ordem.
+    next_chunk_to_write: u64,
+    last_acceptable_chunk: u32,
+    last_chunk_read: u32,
+}
+
+impl FileReceiver {
+    /// Prepara o recebimento de um arquivo de `file_size` bytes dividido em blocos de `chunk_size` bytes, aceitando
+    /// até `receive_window` blocos além do último confirmado.
+    pub fn new(file_size: u64, chunk_size: u16, receive_window: u16) -> FileReceiver {
+        let expected_chunks = FileReceiver::expected_chunks(file_size, chunk_size);
+        debug!(expected_chunks, "Quantidade de blocos esperados");
+
+        FileReceiver {
+            expected_chunks,
+            chunk_size: chunk_size as usize,
+            last_chunk_size: (file_size % chunk_size as u64) as usize,
+            // A janela vai de `last_chunk_read` a `last_acceptable_chunk`, inclusive.
+            pending: vec![None; receive_window as usize + 1],
+            next_chunk_to_write: 0,
+            last_acceptable_chunk: receive_window as u32,
+            last_chunk_read: 0,
+        }
+    }
+
+    /// Quantidade de blocos em que um arquivo de `file_size` bytes é dividido. O último bloco tem o resto da divisão,
+    /// e fica vazio quando o tamanho do arquivo é múltiplo de `chunk_size`.
+    pub fn expected_chunks(file_size: u64, chunk_size: u16) -> u64 {
+        (file_size / chunk_size as u64) + 1
+    }
+
+    pub fn on_chunk(&mut self, sequence_number: u32, data: Vec<u8>) -> ChunkOutcome {
+        let mut outcome = ChunkOutcome {
+            acks: Vec::new(),
+            ready_to_write: Vec::new(),
+            finished: false,
+            duplicate: false,
+            dropped: false,
+            malformed: false,
+        };
+
+        if sequence_number as u64 >= self.expected_chunks {
+            debug!(
+                chunk = sequence_number,
+                "Bloco não pertence ao arquivo, descartando"
+            );
+            outcome.dropped = true;
+            return outcome;
+        }
+
+        // Um bloco de outro tamanho corromperia o arquivo, e um bloco maior faria o arquivo escrito exceder o tamanho
+        // anunciado pelo emissor.
+        let expected_size = if sequence_number as u64 == self.expected_chunks - 1 {
+            self.last_chunk_size
+        } else {
+            self.chunk_size
+        };
+        if data.len() != expected_size {
+            debug!(
+                chunk = sequence_number,
+                bytes = data.len(),
+                expected_size,
+                "Bloco com tamanho inesperado, descartando"
+            );
+            outcome.malformed = true;
+            return outcome;
+        }
+
+        let received_chunk_is_in_window = self.last_chunk_read <= sequence_number
+            && sequence_number <= self.last_acceptable_chunk;
+        if !received_chunk_is_in_window {
+            trace!(
+                chunk = sequence_number,
+                "Bloco recebido está fora da janela"
+            );
+            outcome.duplicate = sequence_number < self.last_chunk_read;
+            outcome.dropped = !outcome.duplicate;
+
+            // Esse ack é enviado pois o emissor pode estar esperando um ack que foi perdido,
+            // e está retransmitindo blocos que para o receptor já estão "acked"
+            if self.last_chunk_read > 0 {
+                let ack_idx = self.last_chunk_read - 1;
+                trace!(
+                    ack = ack_idx,
+                    chunk = sequence_number,
+                    "Enviando ack para o último bloco válido recebido"
+                );
+                outcome.acks.push(ack_idx);
+            }
+            return outcome;
+        }
+
+        trace!(
+            chunk = sequence_number,
+            laf = self.last_acceptable_chunk,
+            lfr = self.last_chunk_read,
+            "Bloco recebido dentro da janela"
+        );
+        if (sequence_number as u64) < self.next_chunk_to_write {
+            // Já entregue para a escrita.
+            outcome.duplicate = true;
+        } else {
+            let slot = self.slot(sequence_number as u64);
+            outcome.duplicate = slot.is_some();
+            *slot = Some(data);
+        }
+
+        while self.next_chunk_to_write < self.expected_chunks {
+            match self.slot(self.next_chunk_to_write).take() {
+                Some(chunk) => outcome.ready_to_write.push(chunk),
+                None => break,
+            }
+            self.next_chunk_to_write += 1;
+        }
+
+        // Os blocos são entregues para escrita de forma contígua, então todos foram recebidos quando o último foi
+        // entregue.
+        let all_received = self.next_chunk_to_write == self.expected_chunks;
+        if all_received {
+            debug!("Todos os blocos recebidos. Enviando ack para o último.");
+            outcome.acks.push((self.expected_chunks - 1) as u32);
+            outcome.finished = true;
+        } else if self.next_chunk_to_write > 0 {
+            // Todo bloco dentro da janela gera um ack cumulativo do último bloco recebido de forma contígua. Um bloco
+            // que chega depois de uma falta repete o ack anterior, e os acks duplicados permitem ao emissor
+            // retransmitir o bloco que falta sem esperar pelo timeout.
+            let idx = self.next_chunk_to_write as u32;
+            let ack_idx = idx - 1;
+            trace!(ack = ack_idx, "Enviando ack");
+            outcome.acks.push(ack_idx);
+
+            let amt = idx - self.last_chunk_read;
+            if amt > 0 {
+                self.last_chunk_read += amt;
+                self.last_acceptable_chunk = self.last_acceptable_chunk.saturating_add(amt);
+
+                trace!(
+                    laf = self.last_acceptable_chunk,
+                    lfr = self.last_chunk_read,
+                    "Janela deslocada"
+                );
+            }
+        }
+
+        outcome
+    }
+
+    fn slot(&mut self, chunk: u64) -> &mut Option<Vec<u8>> {
+        let len = self.pending.len() as u64;
+        &mut self.pending[(chunk % len) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileReceiver;
+
+    /// Conteúdo do bloco `n` nos testes.
+    fn chunk(n: u32) -> Vec<u8> {
+        vec![n as u8; 4]
+    }
+
+    #[test]
+    fn in_order_chunks_are_written_and_acked() {
+        // 10 bytes em blocos de 4: dois blocos cheios e um de 2 bytes.
+        let mut receiver = FileReceiver::new(10, 4, 10);
+
+        let outcome = receiver.on_chunk(0, chunk(0));
+        assert_eq!(outcome.acks, vec![0]);
+        assert_eq!(outcome.ready_to_write, vec![chunk(0)]);
+
+        let outcome = receiver.on_chunk(1, chunk(1));
+        assert_eq!(outcome.acks, vec![1]);
+        assert!(!outcome.finished);
+
+        let outcome = receiver.on_chunk(2, vec![2; 2]);
+        assert_eq!(outcome.acks, vec![2]);
+        assert_eq!(outcome.ready_to_write, vec![vec![2; 2]]);
+        assert!(outcome.finished);
+    }
+
+    #[test]
+    fn file_size_multiple_of_chunk_size_ends_with_an_empty_chunk() {
+        assert_eq!(FileReceiver::expected_chunks(8, 4), 3);
+        assert_eq!(FileReceiver::expected_chunks(0, 4), 1);
+    }
+
+    #[test]
+    fn empty_file_is_a_single_empty_chunk() {
+        let mut receiver = FileReceiver::new(0, 4, 10);
+        let outcome = receiver.on_chunk(0, Vec::new());
+        assert!(outcome.finished);
+        assert_eq!(outcome.acks, vec![0]);
+    }
+
+    #[test]
+    fn out_of_order_chunks_wait_for_the_gap() {
+        let mut receiver = FileReceiver::new(160, 4, 10);
+        receiver.on_chunk(0, chunk(0));
+
+        // Cada bloco depois da falta gera um ack duplicado do último bloco contíguo, que indica a falta ao emissor.
+        let outcome = receiver.on_chunk(2, chunk(2));
+        assert!(outcome.ready_to_write.is_empty());
+        assert_eq!(outcome.acks, vec![0]);
+        let outcome = receiver.on_chunk(3, chunk(3));
+        assert!(outcome.ready_to_write.is_empty());
+        assert_eq!(outcome.acks, vec![0]);
+
+        // O bloco que faltava libera os seguintes, e o ack cumulativo confirma todos.
+        let outcome = receiver.on_chunk(1, chunk(1));
+        assert_eq!(outcome.ready_to_write, vec![chunk(1), chunk(2), chunk(3)]);
+        assert_eq!(outcome.acks, vec![3]);
+        assert!(!outcome.duplicate);
+    }
+
+    #[test]
+    fn chunks_beyond_the_window_are_dropped() {
+        let mut receiver = FileReceiver::new(1600, 4, 10);
+        receiver.on_chunk(0, chunk(0));
+
+        // A janela vai do último bloco confirmado, 1, até 1 + 10.
+        let outcome = receiver.on_chunk(12, chunk(12));
+        assert!(outcome.dropped);
+        assert!(!outcome.duplicate);
+        assert_eq!(outcome.acks, vec![0]);
+
+        let outcome = receiver.on_chunk(11, chunk(11));
+        assert!(!outcome.dropped);
+        assert_eq!(outcome.acks, vec![0]);
+
+        let outcome = receiver.on_chunk(500, chunk(0));
+        assert!(outcome.dropped);
+        assert!(outcome.acks.is_empty());
+    }
+
+    #[test]
+    fn window_slides_with_the_acks() {
+        let mut receiver = FileReceiver::new(1600, 4, 10);
+        for n in 0..20 {
+            let outcome = receiver.on_chunk(n, chunk(n));
+            assert_eq!(outcome.acks, vec![n]);
+            assert_eq!(outcome.ready_to_write, vec![chunk(n)]);
+        }
+        assert!(!receiver.on_chunk(30, chunk(30)).dropped);
+        assert!(receiver.on_chunk(31, chunk(31)).dropped);
+    }
+
+    #[test]
+    fn retransmitted_chunks_are_reported_as_duplicates() {
+        let mut receiver = FileReceiver::new(1600, 4, 10);
+        receiver.on_chunk(0, chunk(0));
+        receiver.on_chunk(1, chunk(1));
+
+        // Bloco já confirmado: o último ack é repetido, para o caso de o anterior ter se perdido.
+        let outcome = receiver.on_chunk(0, chunk(0));
+        assert!(outcome.duplicate);
+        assert!(!outcome.dropped);
+        assert_eq!(outcome.acks, vec![1]);
+        assert!(outcome.ready_to_write.is_empty());
+
+        // Bloco recebido fora de ordem e ainda não entregue.
+        receiver.on_chunk(4, chunk(4));
+        let outcome = receiver.on_chunk(4, chunk(4));
+        assert!(outcome.duplicate);
+        assert!(outcome.ready_to_write.is_empty());
+    }
+
+    #[test]
+    fn pending_chunks_use_only_the_window_slots() {
+        // Os blocos guardados ocupam posições `n % (janela + 1)`, e nunca se sobrepõem.
+        let mut receiver = FileReceiver::new(4000, 4, 3);
+        let mut written = Vec::new();
+        for round in 0..50u32 {
+            let base = round * 4;
+            for n in [base + 3, base + 1, base + 2, base] {
+                written.extend(receiver.on_chunk(n, chunk(n)).ready_to_write);
+            }
+            assert_eq!(receiver.pending.len(), 4);
+        }
+        let expected: Vec<_> = (0..200).map(chunk).collect();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn chunks_with_unexpected_length_are_rejected() {
+        // 10 bytes em blocos de 4: o último bloco tem 2 bytes.
+        let mut receiver = FileReceiver::new(10, 4, 10);
+
+        for data in [vec![0; 65_000], vec![0; 5], vec![0; 3], Vec::new()] {
+            let outcome = receiver.on_chunk(0, data);
+            assert!(outcome.malformed);
+            assert!(outcome.acks.is_empty());
+            assert!(outcome.ready_to_write.is_empty());
+        }
+        assert_eq!(receiver.on_chunk(0, chunk(0)).acks, vec![0]);
+        assert_eq!(receiver.on_chunk(1, chunk(1)).acks, vec![1]);
+
+        // O último bloco deve ter exatamente o restante do arquivo.
+        assert!(receiver.on_chunk(2, chunk(2)).malformed);
+        assert!(receiver.on_chunk(2, vec![2; 1]).malformed);
+        let outcome = receiver.on_chunk(2, vec![2; 2]);
+        assert!(!outcome.malformed);
+        assert!(outcome.finished);
+    }
+}
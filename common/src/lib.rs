@@ -1,8 +1,38 @@
 mod byte_utils;
 pub use byte_utils::{u16_from_u8_array, u32_from_u8_array, u64_from_u8_array};
 
+pub mod congestion_control;
+
 mod message;
-pub use message::{ChunkData, FileData, Message, MessageCreationError};
+pub use message::{
+    AckData, ChunkData, ConnectionData, ErrorCode, FileData, GetData, Message,
+    MessageCreationError, CHUNK_SIZE, FILE_HEADER_SIZE, MAX_CHUNK_SIZE, MAX_DATAGRAM_SIZE,
+    MAX_PATH_LENGTH,
+};
+
+mod file_receiver;
+pub use file_receiver::{ChunkOutcome, FileReceiver};
+
+mod duration;
+pub use duration::parse_duration;
+
+mod observer;
+pub use observer::{NoopObserver, TransferInfo, TransferObserver};
+
+mod pacer;
+mod rtt_estimator;
+
+mod stats;
+pub use stats::TransferStats;
+
+mod sender;
+pub use sender::{SendOptions, Sender};
 
 mod network_utils;
-pub use network_utils::{receive_message, send_message, GenericError};
+pub use network_utils::{parse_message, receive_message, send_message, GenericError};
+
+#[cfg(feature = "async")]
+pub mod async_network_utils;
+
+#[cfg(feature = "logging")]
+pub mod logging;
@@ -0,0 +1,102 @@
+//! Configuração do registro de eventos dos binários: nível pela variável `RUST_LOG`, pela quantidade de `--verbose` ou
+//! pela configuração do programa, e saída em texto ou em JSON (uma linha por evento), sempre na saída de erro.
+
+use std::io::{stderr, IsTerminal};
+use std::str::FromStr;
+
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Formato das linhas de log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<LogFormat, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("Formato de log desconhecido (opções: text, json)"),
+        }
+    }
+}
+
+/// Permite trocar o filtro de níveis depois da instalação, como na releitura da configuração do servidor.
+pub struct LogHandle {
+    handle: Option<reload::Handle<EnvFilter, Registry>>,
+}
+
+impl LogHandle {
+    /// Recalcula o filtro com as mesmas regras de `init`, usando `default_level` como novo nível padrão.
+    pub fn set_default_level(&self, verbosity: u8, default_level: &str) -> Result<(), String> {
+        let filter = build_filter(verbosity, default_level)?;
+        match &self.handle {
+            Some(handle) => handle.reload(filter).map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Verifica se `level` é um filtro válido, no formato de `RUST_LOG` (por exemplo, "info" ou "info,servidor=debug").
+pub fn validate_level(level: &str) -> Result<(), String> {
+    EnvFilter::try_new(level)
+        .map(|_filter| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Instala o registro de eventos global.
+///
+/// Sem `--verbose` (`verbosity` 0), o filtro vem de `RUST_LOG` e, na sua ausência, de `default_level`, como "info".
+/// Cada `--verbose` reduz o nível mínimo: `debug` com um e `trace` a partir de dois, ignorando os demais.
+pub fn init(verbosity: u8, default_level: &str, format: LogFormat) -> LogHandle {
+    let filter = build_filter(verbosity, default_level).unwrap_or_else(|e| {
+        eprintln!("Filtro de log inválido ({}), usando \"info\"", e);
+        EnvFilter::new("info")
+    });
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let ansi = stderr().is_terminal();
+    let text = (format == LogFormat::Text).then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(stderr)
+            .with_ansi(ansi)
+    });
+    let json = (format == LogFormat::Json).then(|| {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(stderr)
+            .with_ansi(ansi)
+    });
+
+    match tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .try_init()
+    {
+        Ok(()) => LogHandle {
+            handle: Some(handle),
+        },
+        Err(e) => {
+            eprintln!("Falha ao configurar o registro de eventos: {}", e);
+            LogHandle { handle: None }
+        }
+    }
+}
+
+fn build_filter(verbosity: u8, default_level: &str) -> Result<EnvFilter, String> {
+    match verbosity {
+        0 => match EnvFilter::try_from_default_env() {
+            Ok(filter) => Ok(filter),
+            Err(_e) => EnvFilter::try_new(default_level).map_err(|e| e.to_string()),
+        },
+        1 => Ok(EnvFilter::new("debug")),
+        _ => Ok(EnvFilter::new("trace")),
+    }
+}
@@ -1,26 +1,165 @@
 use std::str;
 use std::{error::Error, fmt};
 
+use tracing::debug;
+
 use crate::byte_utils;
 
+/// Tamanho padrão, em bytes, do conteúdo de arquivo transportado em cada mensagem "File".
+pub const CHUNK_SIZE: usize = 1000;
+/// Maior datagrama UDP que pode ser enviado.
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+/// Tamanho do cabeçalho de uma mensagem "File": tipo (2 bytes), sessão (4), token (8), número de sequência (4) e
+/// tamanho do conteúdo (2).
+pub const FILE_HEADER_SIZE: usize = 20;
+/// Maior conteúdo que cabe numa mensagem "File" enviada num único datagrama, descontado o cabeçalho.
+pub const MAX_CHUNK_SIZE: usize = MAX_DATAGRAM_SIZE - FILE_HEADER_SIZE;
+
+/// Maior caminho relativo, em bytes, aceito na mensagem "InfoPath".
+pub const MAX_PATH_LENGTH: usize = 1000;
+
+/// Bytes da mensagem "InfoPath" antes do caminho: tipo, tamanho do caminho, tamanho do arquivo e tamanho dos blocos.
+const INFO_PATH_HEADER_SIZE: usize = 14;
+
+pub struct ConnectionData {
+    pub port: u32,
+    /// Quantidade de blocos que o servidor aceita além do último bloco confirmado.
+    pub receive_window: u16,
+    /// Identificador da sessão, repetido pelo cliente em cada mensagem "File" para que o servidor possa distribuir os
+    /// datagramas recebidos numa porta compartilhada.
+    pub session_id: u32,
+    /// Valor secreto que o cliente deve repetir em cada mensagem "File", quando o servidor o exige; zero, caso
+    /// contrário.
+    pub token: u64,
+}
+
 pub struct FileData {
     pub filename: String,
     pub file_size: u64,
+    /// Tamanho dos blocos em que o cliente divide o arquivo.
+    pub chunk_size: u16,
+}
+
+/// Pedido de download de um arquivo do servidor.
+pub struct GetData {
+    /// Nome do arquivo na raiz do diretório de saída do servidor, com até 15 bytes. Os arquivos em subdiretórios,
+    /// enviados com "InfoPath", não podem ser pedidos.
+    pub filename: String,
+    /// Tamanho dos blocos em que o servidor deve dividir o arquivo.
+    pub chunk_size: u16,
+    /// Quantidade de blocos que o cliente aceita além do último bloco confirmado.
+    pub receive_window: u16,
+    /// Porta UDP do cliente para a qual os blocos são enviados, no mesmo endereço IP da conexão TCP.
+    pub port: u16,
 }
 
 pub struct ChunkData {
+    pub session_id: u32,
+    pub token: u64,
     pub sequence_number: u32,
     pub payload_size: u16,
     pub data: Vec<u8>,
 }
+
+/// Motivo informado na mensagem "Error", que encerra a sessão.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// O servidor não tem portas UDP livres para a sessão.
+    NoUdpPortAvailable,
+    /// O cliente não anunciou o arquivo dentro do prazo do handshake.
+    HandshakeTimeout,
+    /// O cliente ficou sem enviar mensagens nem blocos por mais tempo que o permitido.
+    IdleTimeout,
+    /// A transferência excedeu a duração máxima permitida pelo servidor.
+    TransferTimeout,
+    /// O servidor está sendo encerrado e interrompeu a sessão.
+    ShuttingDown,
+    /// O arquivo pedido pelo cliente não existe no servidor.
+    FileNotFound,
+    /// O caminho anunciado pelo cliente não é relativo ou sai do diretório de saída do servidor.
+    InvalidPath,
+    /// O arquivo anunciado pelo cliente excede o tamanho máximo aceito pelo servidor.
+    FileTooLarge,
+    /// Código não reconhecido por esta versão do protocolo.
+    Unknown(u16),
+}
+
+impl ErrorCode {
+    fn to_u16(self) -> u16 {
+        match self {
+            ErrorCode::NoUdpPortAvailable => 1,
+            ErrorCode::HandshakeTimeout => 2,
+            ErrorCode::IdleTimeout => 3,
+            ErrorCode::TransferTimeout => 4,
+            ErrorCode::ShuttingDown => 5,
+            ErrorCode::FileNotFound => 6,
+            ErrorCode::InvalidPath => 7,
+            ErrorCode::FileTooLarge => 8,
+            ErrorCode::Unknown(code) => code,
+        }
+    }
+
+    fn from_u16(code: u16) -> ErrorCode {
+        match code {
+            1 => ErrorCode::NoUdpPortAvailable,
+            2 => ErrorCode::HandshakeTimeout,
+            3 => ErrorCode::IdleTimeout,
+            4 => ErrorCode::TransferTimeout,
+            5 => ErrorCode::ShuttingDown,
+            6 => ErrorCode::FileNotFound,
+            7 => ErrorCode::InvalidPath,
+            8 => ErrorCode::FileTooLarge,
+            code => ErrorCode::Unknown(code),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorCode::NoUdpPortAvailable => write!(f, "Nenhuma porta UDP disponível no servidor"),
+            ErrorCode::HandshakeTimeout => write!(f, "Prazo do handshake excedido"),
+            ErrorCode::IdleTimeout => write!(f, "Prazo de inatividade excedido"),
+            ErrorCode::TransferTimeout => write!(f, "Tempo máximo de transferência excedido"),
+            ErrorCode::ShuttingDown => write!(f, "O servidor está sendo encerrado"),
+            ErrorCode::FileNotFound => write!(f, "Arquivo não encontrado no servidor"),
+            ErrorCode::InvalidPath => write!(f, "Caminho de arquivo inválido"),
+            ErrorCode::FileTooLarge => write!(f, "Arquivo maior que o permitido pelo servidor"),
+            ErrorCode::Unknown(code) => write!(f, "Erro desconhecido (código {})", code),
+        }
+    }
+}
+
+pub struct AckData {
+    pub sequence_number: u32,
+    /// Janela de recepção anunciada pelo servidor no momento do ack.
+    pub receive_window: u16,
+}
 pub enum Message {
     Hello,
-    Connection(u32),
+    Connection(ConnectionData),
     InfoFile(FileData),
     Ok,
     End,
     File(ChunkData),
-    Ack(u32),
+    Ack(AckData),
+    Error(ErrorCode),
+    /// Enviada pelo cliente para cancelar o envio; o servidor encerra a sessão e remove o arquivo parcial.
+    Abort,
+    /// Enviada pelo cliente ao suspender o envio dos blocos; enquanto durar a pausa, a sessão não expira por
+    /// inatividade.
+    Pause,
+    /// Enviada pelo cliente ao retomar o envio, na mesma sessão, depois de uma mensagem "Pause".
+    Resume,
+    /// Enviada pelo cliente, no lugar de "InfoFile", para baixar um arquivo. O servidor responde com "InfoFile" e
+    /// envia os blocos; os acks e a mensagem "End" passam a ser enviados pelo cliente.
+    Get(GetData),
+    /// Como "InfoFile", mas com o caminho do arquivo relativo ao diretório de saída do servidor, com os diretórios
+    /// separados por "/". É a única mensagem de tamanho variável: o tamanho do caminho vem logo depois do tipo.
+    InfoPath(FileData),
+    /// Enviada pelo cliente, depois da mensagem "End" de uma transferência, para encerrar a sessão. Sem ela, o
+    /// servidor aguarda outra mensagem "InfoFile" ou "Get" na mesma sessão e com a mesma porta UDP.
+    Close,
 }
 
 #[derive(Debug)]
@@ -49,6 +188,168 @@ impl Error for MessageCreationError {
 }
 
 impl Message {
+    /// Retorna quantos bytes do início de uma mensagem de controle são necessários para determinar o seu tamanho com
+    /// `length_for_header`.
+    pub fn header_length(message_type_byte: u8) -> usize {
+        match message_type_byte {
+            14 => 4,
+            _ => 2,
+        }
+    }
+
+    /// Retorna o tamanho total, em bytes, da mensagem de controle que começa com `header`, que deve ter os
+    /// `header_length` bytes do tipo da mensagem.
+    pub fn length_for_header(header: &[u8]) -> Result<usize, MessageCreationError> {
+        match header {
+            [_, 14, high, low] => {
+                let path_length = u16::from_be_bytes([*high, *low]) as usize;
+                if path_length > MAX_PATH_LENGTH {
+                    return Err(MessageCreationError::new("Caminho de arquivo muito longo"));
+                }
+                Ok(INFO_PATH_HEADER_SIZE + path_length)
+            }
+            [_, message_type, ..] => Message::length_for_type(*message_type),
+            _ => Err(MessageCreationError::new(
+                "Cabeçalho insuficiente para determinar o tamanho da mensagem",
+            )),
+        }
+    }
+
+    /// Retorna o tamanho total, em bytes, das mensagens de controle (trafegadas via TCP) do tipo informado. Não se
+    /// aplica à mensagem "InfoPath", de tamanho variável; veja `length_for_header`.
+    pub fn length_for_type(message_type_byte: u8) -> Result<usize, MessageCreationError> {
+        match message_type_byte {
+            1 | 4 | 5 | 9 | 10 | 11 | 13 => Ok(2),
+            7 => Ok(8),
+            2 => Ok(20),
+            3 => Ok(27),
+            8 => Ok(4),
+            12 => Ok(23),
+            other => {
+                debug!(
+                    message_type = other,
+                    "Tipo de mensagem de controle desconhecido"
+                );
+                Err(MessageCreationError::new(
+                    "Tipo de mensagem de controle desconhecido.",
+                ))
+            }
+        }
+    }
+
+    /// Cria uma mensagem "InfoPath", recusando caminhos com mais de `MAX_PATH_LENGTH` bytes.
+    pub fn info_path(file_data: FileData) -> Result<Message, MessageCreationError> {
+        if file_data.filename.len() > MAX_PATH_LENGTH {
+            return Err(MessageCreationError::new("Caminho de arquivo muito longo"));
+        }
+        Ok(Message::InfoPath(file_data))
+    }
+
+    /// Serializa a mensagem no formato usado na rede. Nomes de arquivo com mais de 15 bytes são truncados.
+    ///
+    /// # Panics
+    ///
+    /// Se o caminho de uma mensagem "InfoPath" tiver mais de `MAX_PATH_LENGTH` bytes; `Message::info_path` recusa
+    /// esses caminhos.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Message::Hello => vec![0, 1],
+            Message::Connection(ConnectionData {
+                port,
+                receive_window,
+                session_id,
+                token,
+            }) => {
+                let mut connection: Vec<u8> = vec![0, 2];
+                connection.extend(port.to_be_bytes().iter());
+                connection.extend(receive_window.to_be_bytes().iter());
+                connection.extend(session_id.to_be_bytes().iter());
+                connection.extend(token.to_be_bytes().iter());
+                connection
+            }
+            Message::InfoFile(FileData {
+                filename,
+                file_size,
+                chunk_size,
+            }) => {
+                let mut info_file: Vec<u8> = vec![0, 3];
+                let filename = &filename.as_bytes()[..filename.len().min(15)];
+                info_file.extend(std::iter::repeat_n(0, 15 - filename.len()));
+                info_file.extend(filename.iter());
+                info_file.extend(file_size.to_be_bytes().iter());
+                info_file.extend(chunk_size.to_be_bytes().iter());
+                info_file
+            }
+            Message::Ok => vec![0, 4],
+            Message::End => vec![0, 5],
+            Message::File(ChunkData {
+                session_id,
+                token,
+                sequence_number,
+                payload_size,
+                data,
+            }) => {
+                let mut file: Vec<u8> = vec![0, 6];
+                file.extend(session_id.to_be_bytes().iter());
+                file.extend(token.to_be_bytes().iter());
+                file.extend(sequence_number.to_be_bytes().iter());
+                file.extend(payload_size.to_be_bytes().iter());
+                file.extend(data.iter());
+                file
+            }
+            Message::Ack(AckData {
+                sequence_number,
+                receive_window,
+            }) => {
+                let mut ack: Vec<u8> = vec![0, 7];
+                ack.extend(sequence_number.to_be_bytes().iter());
+                ack.extend(receive_window.to_be_bytes().iter());
+                ack
+            }
+            Message::Error(code) => {
+                let mut error: Vec<u8> = vec![0, 8];
+                error.extend(code.to_u16().to_be_bytes().iter());
+                error
+            }
+            Message::Abort => vec![0, 9],
+            Message::Pause => vec![0, 10],
+            Message::Resume => vec![0, 11],
+            Message::Get(GetData {
+                filename,
+                chunk_size,
+                receive_window,
+                port,
+            }) => {
+                let mut get: Vec<u8> = vec![0, 12];
+                let filename = &filename.as_bytes()[..filename.len().min(15)];
+                get.extend(std::iter::repeat_n(0, 15 - filename.len()));
+                get.extend(filename.iter());
+                get.extend(chunk_size.to_be_bytes().iter());
+                get.extend(receive_window.to_be_bytes().iter());
+                get.extend(port.to_be_bytes().iter());
+                get
+            }
+            Message::Close => vec![0, 13],
+            Message::InfoPath(FileData {
+                filename,
+                file_size,
+                chunk_size,
+            }) => {
+                let path = filename.as_bytes();
+                assert!(
+                    path.len() <= MAX_PATH_LENGTH,
+                    "Caminho de arquivo muito longo"
+                );
+                let mut info_path: Vec<u8> = vec![0, 14];
+                info_path.extend((path.len() as u16).to_be_bytes().iter());
+                info_path.extend(file_size.to_be_bytes().iter());
+                info_path.extend(chunk_size.to_be_bytes().iter());
+                info_path.extend(path.iter());
+                info_path
+            }
+        }
+    }
+
     pub fn new(message: &[u8], bytes_read: usize) -> Result<Message, MessageCreationError> {
         if bytes_read < 2 {
             return Err(MessageCreationError::new("Foram lidos menos de 2 bytes, o que é insuficiente para determinar o tipo de mensagem"));
@@ -57,14 +358,21 @@ impl Message {
 
         match message_type_byte {
             1 => Ok(Self::Hello),
-            2 => create_connection(bytes_read, &message),
-            3 => create_info_file(bytes_read, &message),
+            2 => create_connection(bytes_read, message),
+            3 => create_info_file(bytes_read, message),
             4 => Ok(Self::Ok),
             5 => Ok(Self::End),
-            6 => create_file(bytes_read, &message),
-            7 => create_ack(bytes_read, &message),
+            6 => create_file(bytes_read, message),
+            7 => create_ack(bytes_read, message),
+            8 => create_error(bytes_read, message),
+            9 => Ok(Self::Abort),
+            10 => Ok(Self::Pause),
+            11 => Ok(Self::Resume),
+            12 => create_get(bytes_read, message),
+            13 => Ok(Self::Close),
+            14 => create_info_path(bytes_read, message),
             other => {
-                println!("Tipo de mensagem ({}) desconhecido.", other);
+                debug!(message_type = other, "Tipo de mensagem desconhecido");
                 Err(MessageCreationError::new("Tipo de mensagem desconhecido."))
             }
         }
@@ -76,15 +384,23 @@ fn create_connection(
     bytes_read: usize,
     message_type: &[u8],
 ) -> Result<Message, MessageCreationError> {
-    if bytes_read < 6 {
+    if bytes_read < 20 {
         return Err(MessageCreationError::new(
-            "Foram lidos menos de 6 bytes para uma mensagem que deve conter no mínimo 6 bytes",
+            "Foram lidos menos de 20 bytes para uma mensagem que deve conter no mínimo 20 bytes",
         ));
     }
     let array = &message_type[2..6];
     let port = byte_utils::u32_from_u8_array(array);
+    let receive_window = byte_utils::u16_from_u8_array(&message_type[6..8]);
+    let session_id = byte_utils::u32_from_u8_array(&message_type[8..12]);
+    let token = byte_utils::u64_from_u8_array(&message_type[12..20]);
 
-    Ok(Message::Connection(port))
+    Ok(Message::Connection(ConnectionData {
+        port,
+        receive_window,
+        session_id,
+        token,
+    }))
 }
 
 /// Cria uma mensagem do tipo "Info file"
@@ -92,9 +408,9 @@ fn create_info_file(
     bytes_read: usize,
     message_type: &[u8],
 ) -> Result<Message, MessageCreationError> {
-    if bytes_read < 25 {
+    if bytes_read < 27 {
         return Err(MessageCreationError::new(
-            "Foram lidos menos de 25 bytes para uma mensagem que deve conter no mínimo 25 bytes",
+            "Foram lidos menos de 27 bytes para uma mensagem que deve conter no mínimo 27 bytes",
         ));
     }
     let filename = match str::from_utf8(&message_type[2..17]) {
@@ -107,27 +423,97 @@ fn create_info_file(
     };
 
     let file_size = byte_utils::u64_from_u8_array(&message_type[17..25]);
+    let chunk_size = byte_utils::u16_from_u8_array(&message_type[25..27]);
 
     Ok(Message::InfoFile(FileData {
         filename,
         file_size,
+        chunk_size,
+    }))
+}
+
+/// Cria uma mensagem do tipo "InfoPath"
+fn create_info_path(
+    bytes_read: usize,
+    message_type: &[u8],
+) -> Result<Message, MessageCreationError> {
+    if bytes_read < INFO_PATH_HEADER_SIZE {
+        return Err(MessageCreationError::new(
+            "Foram lidos menos de 14 bytes para uma mensagem que deve conter no mínimo 14 bytes",
+        ));
+    }
+    let path_length = byte_utils::u16_from_u8_array(&message_type[2..4]) as usize;
+    if bytes_read < INFO_PATH_HEADER_SIZE + path_length {
+        return Err(MessageCreationError::new(
+            "Foram lidos menos bytes que o tamanho do caminho informado",
+        ));
+    }
+    let filename = match str::from_utf8(
+        &message_type[INFO_PATH_HEADER_SIZE..INFO_PATH_HEADER_SIZE + path_length],
+    ) {
+        Ok(str) => String::from(str),
+        Err(_e) => {
+            return Err(MessageCreationError::new(
+                "Falha ao converter bytes para string",
+            ))
+        }
+    };
+
+    let file_size = byte_utils::u64_from_u8_array(&message_type[4..12]);
+    let chunk_size = byte_utils::u16_from_u8_array(&message_type[12..14]);
+
+    Ok(Message::InfoPath(FileData {
+        filename,
+        file_size,
+        chunk_size,
+    }))
+}
+
+/// Cria uma mensagem do tipo "Get"
+fn create_get(bytes_read: usize, message_type: &[u8]) -> Result<Message, MessageCreationError> {
+    if bytes_read < 23 {
+        return Err(MessageCreationError::new(
+            "Foram lidos menos de 23 bytes para uma mensagem que deve conter no mínimo 23 bytes",
+        ));
+    }
+    let filename = match str::from_utf8(&message_type[2..17]) {
+        Ok(str) => String::from(str.trim_matches(char::from(0))),
+        Err(_e) => {
+            return Err(MessageCreationError::new(
+                "Falha ao converter bytes para string",
+            ))
+        }
+    };
+
+    let chunk_size = byte_utils::u16_from_u8_array(&message_type[17..19]);
+    let receive_window = byte_utils::u16_from_u8_array(&message_type[19..21]);
+    let port = byte_utils::u16_from_u8_array(&message_type[21..23]);
+
+    Ok(Message::Get(GetData {
+        filename,
+        chunk_size,
+        receive_window,
+        port,
     }))
 }
 
 /// Cria uma mensagem do tipo "File"
 fn create_file(bytes_read: usize, message_type: &[u8]) -> Result<Message, MessageCreationError> {
-    if bytes_read < 8 {
+    if bytes_read < FILE_HEADER_SIZE {
         return Err(MessageCreationError::new(
-            "Foram lidos menos de 8 bytes para uma mensagem que deve conter no mínimo 8 bytes",
+            "Foram lidos menos de 20 bytes para uma mensagem que deve conter no mínimo 20 bytes",
         ));
     }
 
-    let sequence_number = byte_utils::u32_from_u8_array(&message_type[2..6]);
-    let payload_size = byte_utils::u16_from_u8_array(&message_type[6..8]);
+    let session_id = byte_utils::u32_from_u8_array(&message_type[2..6]);
+    let token = byte_utils::u64_from_u8_array(&message_type[6..14]);
+    let sequence_number = byte_utils::u32_from_u8_array(&message_type[14..18]);
+    let payload_size = byte_utils::u16_from_u8_array(&message_type[18..20]);
 
-    // TODO: avoid clone
-    let file_content = (&message_type[8..bytes_read].to_vec()).clone();
+    let file_content = message_type[FILE_HEADER_SIZE..bytes_read].to_vec();
     Ok(Message::File(ChunkData {
+        session_id,
+        token,
         sequence_number,
         payload_size,
         data: file_content,
@@ -136,12 +522,126 @@ fn create_file(bytes_read: usize, message_type: &[u8]) -> Result<Message, Messag
 
 /// Cria uma mensagem do tipo "Ack"
 fn create_ack(bytes_read: usize, message_type: &[u8]) -> Result<Message, MessageCreationError> {
-    if bytes_read < 6 {
+    if bytes_read < 8 {
         return Err(MessageCreationError::new(
-            "Foram lidos menos de 6 bytes para uma mensagem que deve conter no mínimo 6 bytes",
+            "Foram lidos menos de 8 bytes para uma mensagem que deve conter no mínimo 8 bytes",
         ));
     }
 
     let sequence_number = byte_utils::u32_from_u8_array(&message_type[2..6]);
-    Ok(Message::Ack(sequence_number))
+    let receive_window = byte_utils::u16_from_u8_array(&message_type[6..8]);
+    Ok(Message::Ack(AckData {
+        sequence_number,
+        receive_window,
+    }))
+}
+
+/// Cria uma mensagem do tipo "Error"
+fn create_error(bytes_read: usize, message_type: &[u8]) -> Result<Message, MessageCreationError> {
+    if bytes_read < 4 {
+        return Err(MessageCreationError::new(
+            "Foram lidos menos de 4 bytes para uma mensagem que deve conter no mínimo 4 bytes",
+        ));
+    }
+
+    let code = byte_utils::u16_from_u8_array(&message_type[2..4]);
+    Ok(Message::Error(ErrorCode::from_u16(code)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AckData, ConnectionData, FileData, Message, MAX_PATH_LENGTH};
+
+    fn info_path(filename: String) -> Vec<u8> {
+        Message::info_path(FileData {
+            filename,
+            file_size: 1234,
+            chunk_size: 500,
+        })
+        .unwrap()
+        .encode()
+    }
+
+    #[test]
+    fn connection_message_advertises_the_receive_window() {
+        let connection = Message::Connection(ConnectionData {
+            port: 4000,
+            receive_window: 10,
+            session_id: 7,
+            token: 0x0123_4567_89ab_cdef,
+        })
+        .encode();
+        match Message::new(&connection, connection.len()).unwrap() {
+            Message::Connection(data) => {
+                assert_eq!(data.port, 4000);
+                assert_eq!(data.receive_window, 10);
+                assert_eq!(data.session_id, 7);
+                assert_eq!(data.token, 0x0123_4567_89ab_cdef);
+            }
+            _ => panic!("Esperava uma mensagem \"Connection\""),
+        }
+    }
+
+    #[test]
+    fn ack_message_carries_the_current_window() {
+        let ack = Message::Ack(AckData {
+            sequence_number: 41,
+            receive_window: 3,
+        })
+        .encode();
+        match Message::new(&ack, ack.len()).unwrap() {
+            Message::Ack(data) => {
+                assert_eq!(data.sequence_number, 41);
+                assert_eq!(data.receive_window, 3);
+            }
+            _ => panic!("Esperava uma mensagem \"Ack\""),
+        }
+    }
+
+    #[test]
+    fn info_path_round_trip_at_max_path_length() {
+        let path = "a/".repeat(MAX_PATH_LENGTH / 2 - 1) + "bc";
+        assert_eq!(path.len(), MAX_PATH_LENGTH);
+
+        let bytes = info_path(path.clone());
+        let header = &bytes[..Message::header_length(bytes[1])];
+        assert_eq!(Message::length_for_header(header).unwrap(), bytes.len());
+        match Message::new(&bytes, bytes.len()).unwrap() {
+            Message::InfoPath(file_data) => {
+                assert_eq!(file_data.filename, path);
+                assert_eq!(file_data.file_size, 1234);
+                assert_eq!(file_data.chunk_size, 500);
+            }
+            _ => panic!("Esperava uma mensagem \"InfoPath\""),
+        }
+    }
+
+    #[test]
+    fn info_path_above_max_path_length_is_rejected() {
+        let result = Message::info_path(FileData {
+            filename: "x".repeat(MAX_PATH_LENGTH + 1),
+            file_size: 1234,
+            chunk_size: 500,
+        });
+        match result {
+            Err(error) => assert_eq!(error.to_string(), "Caminho de arquivo muito longo"),
+            Ok(_) => panic!("Esperava a recusa do caminho"),
+        }
+
+        // Um cabeçalho que anuncia um caminho maior que o limite é recusado antes que o restante seja lido.
+        let length = ((MAX_PATH_LENGTH + 1) as u16).to_be_bytes();
+        let error = Message::length_for_header(&[0, 14, length[0], length[1]]).unwrap_err();
+        assert_eq!(error.to_string(), "Caminho de arquivo muito longo");
+    }
+
+    #[test]
+    #[should_panic(expected = "Caminho de arquivo muito longo")]
+    fn encoding_an_info_path_above_max_path_length_panics() {
+        Message::InfoPath(FileData {
+            filename: "x".repeat(MAX_PATH_LENGTH + 1),
+            file_size: 1234,
+            chunk_size: 500,
+        })
+        .encode();
+    }
 }
@@ -1,51 +1,100 @@
+use std::fmt;
 use std::io::{Error, ErrorKind, Read, Write};
 use std::net::TcpStream;
 
-use crate::{Message, MessageCreationError};
+use crate::{ErrorCode, Message, MessageCreationError};
 
+#[derive(Debug)]
 pub enum GenericError {
     IO(std::io::Error),
     Logic(MessageCreationError),
+    /// Erro que encerra a sessão e é comunicado ao outro lado na mensagem "Error".
+    Protocol(ErrorCode),
+}
+
+impl fmt::Display for GenericError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GenericError::IO(e) => write!(f, "{}", e),
+            GenericError::Logic(e) => write!(f, "{}", e),
+            GenericError::Protocol(code) => write!(f, "{}", code),
+        }
+    }
+}
+
+impl std::error::Error for GenericError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GenericError::IO(e) => Some(e),
+            GenericError::Logic(e) => Some(e),
+            GenericError::Protocol(_code) => None,
+        }
+    }
 }
 
 impl GenericError {
     /// Transforma um std::io::Error em uma instância de GenericError, para facilitar o uso de Result<T, GenericError>.
     pub fn transform_io<T>(original_result: Result<T, std::io::Error>) -> Result<T, GenericError> {
-        original_result.map_err(|e| GenericError::IO(e))
+        original_result.map_err(GenericError::IO)
     }
 
     /// Transforma um MessageCreationError em uma instância de GenericError, para facilitar o uso de Result<T, GenericError>.
     pub fn transform_logic<T>(
         original_result: Result<T, MessageCreationError>,
     ) -> Result<T, GenericError> {
-        original_result.map_err(|e| GenericError::Logic(e))
+        original_result.map_err(GenericError::Logic)
     }
 }
 
 /// Recebe uma mensagem do socket TCP, e transforma-a numa instância de Message, ou retorna o erro caso algum problema
 /// aconteça (erro de I/O ou lógica).
+///
+/// Como o TCP não preserva os limites das mensagens, são lidos exatamente os bytes de uma mensagem, de acordo com o
+/// tamanho associado ao seu tipo; assim, mensagens enviadas em sequência (como acks) não são perdidas.
 pub fn receive_message(stream: &mut TcpStream) -> Result<Message, GenericError> {
     let mut buffer = [0; 1024];
 
-    let bytes_read = stream
-        .read(&mut buffer)
-        .map_err(|err| GenericError::IO(err));
+    read_exact(stream, &mut buffer[..2])?;
+    let header_length = Message::header_length(buffer[1]);
+    read_exact(stream, &mut buffer[2..header_length])?;
 
-    bytes_read.and_then(|value| {
-        if value == 0 {
-            Err(GenericError::IO(Error::new(
-                ErrorKind::ConnectionAborted,
-                "Conexão fechada",
-            )))
+    let message_length =
+        GenericError::transform_logic(Message::length_for_header(&buffer[..header_length]))?;
+    read_exact(stream, &mut buffer[header_length..message_length])?;
+
+    GenericError::transform_logic(Message::new(&buffer, message_length))
+}
+
+/// Extrai a primeira mensagem de controle de um buffer com bytes lidos de um socket TCP não bloqueante. Retorna a
+/// mensagem e a quantidade de bytes consumidos, ou None caso o buffer ainda não contenha uma mensagem completa.
+pub fn parse_message(buffer: &[u8]) -> Result<Option<(Message, usize)>, MessageCreationError> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+    let header_length = Message::header_length(buffer[1]);
+    if buffer.len() < header_length {
+        return Ok(None);
+    }
+
+    let message_length = Message::length_for_header(&buffer[..header_length])?;
+    if buffer.len() < message_length {
+        return Ok(None);
+    }
+
+    Message::new(&buffer[..message_length], message_length).map(|m| Some((m, message_length)))
+}
+
+fn read_exact(stream: &mut TcpStream, buffer: &mut [u8]) -> Result<(), GenericError> {
+    stream.read_exact(buffer).map_err(|err| {
+        if err.kind() == ErrorKind::UnexpectedEof {
+            GenericError::IO(Error::new(ErrorKind::ConnectionAborted, "Conexão fechada"))
         } else {
-            GenericError::transform_logic(Message::new(&buffer, value))
+            GenericError::IO(err)
         }
     })
 }
 
 /// Envia um array de bytes para o socket TCP, e retorna quantos bytes foram enviados, ou o erro associado.
-pub fn send_message(stream: &mut TcpStream, data: &Vec<u8>) -> Result<usize, Error> {
-    let bytes_written = stream.write(data);
-
-    bytes_written
+pub fn send_message(stream: &mut TcpStream, data: &[u8]) -> Result<usize, Error> {
+    stream.write(data)
 }
@@ -0,0 +1,38 @@
+use std::net::SocketAddr;
+
+use crate::GenericError;
+
+/// Dados de uma transferência, conhecidos ao fim do handshake.
+#[derive(Clone, Debug)]
+pub struct TransferInfo {
+    /// Endereço do outro lado da conexão de controle.
+    pub peer: SocketAddr,
+    pub filename: String,
+    pub file_size: u64,
+}
+
+/// Acompanha o andamento de uma transferência. Os métodos são chamados pelo cliente e pelo servidor, na thread do
+/// laço de eventos, e não devem bloquear.
+///
+/// Todos os métodos têm implementação vazia, então basta implementar os eventos de interesse.
+pub trait TransferObserver: Send {
+    /// O handshake terminou e o envio dos blocos vai começar.
+    fn on_handshake(&mut self, _transfer: &TransferInfo) {}
+
+    /// Os blocos até `chunk` foram confirmados, somando `acked_bytes` bytes do arquivo.
+    fn on_chunk_acked(&mut self, _chunk: u32, _acked_bytes: u64) {}
+
+    /// O bloco `chunk` foi retransmitido (no cliente) ou recebido novamente (no servidor).
+    fn on_retransmit(&mut self, _chunk: u32) {}
+
+    /// O arquivo foi transferido e escrito por completo.
+    fn on_complete(&mut self) {}
+
+    /// A transferência foi interrompida por um erro.
+    fn on_error(&mut self, _error: &GenericError) {}
+}
+
+/// Observador que ignora todos os eventos.
+pub struct NoopObserver;
+
+impl TransferObserver for NoopObserver {}
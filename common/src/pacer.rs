@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+/// Quantidade de datagramas que podem ser enviados em rajada, depois de um período ocioso.
+const BURST_DATAGRAMS: f64 = 4.0;
+/// Maior espera retornada por `delay`. Com taxas muito baixas, o envio é reavaliado depois desse tempo, em vez de
+/// esperar por um prazo que não cabe num `Instant`.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Pacer baseado em token bucket: cada datagrama consome tokens equivalentes ao seu tamanho em bytes, e os tokens são
+/// repostos continuamente de acordo com a taxa alvo, o que espaça os envios ao longo do tempo.
+pub struct Pacer {
+    /// Taxa alvo, em bytes por segundo. None desabilita o espaçamento.
+    rate: Option<f64>,
+    tokens: f64,
+    capacity: f64,
+    last_refill: Instant,
+}
+
+impl Pacer {
+    /// Cria um pacer para datagramas de até `datagram_size` bytes.
+    pub fn new(datagram_size: usize) -> Pacer {
+        let capacity = BURST_DATAGRAMS * datagram_size as f64;
+        Pacer {
+            rate: None,
+            tokens: capacity,
+            capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: Option<f64>) {
+        self.refill();
+        self.rate = rate;
+    }
+
+    /// Tempo que falta até que um datagrama de `bytes` bytes possa ser enviado.
+    pub fn delay(&mut self, bytes: usize) -> Duration {
+        self.refill();
+
+        match self.rate {
+            Some(rate) if self.tokens < bytes as f64 => {
+                Duration::try_from_secs_f64((bytes as f64 - self.tokens) / rate)
+                    .map_or(MAX_DELAY, |delay| delay.min(MAX_DELAY))
+            }
+            _ => Duration::from_secs(0),
+        }
+    }
+
+    /// Registra o envio de um datagrama de `bytes` bytes.
+    pub fn consume(&mut self, bytes: usize) {
+        if self.rate.is_some() {
+            self.tokens -= bytes as f64;
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.rate {
+            let elapsed = (now - self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate).min(self.capacity);
+        }
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Pacer, MAX_DELAY};
+
+    #[test]
+    fn delay_follows_the_rate_once_the_burst_is_spent() {
+        let mut pacer = Pacer::new(1000);
+        pacer.set_rate(Some(1000.0));
+        for _ in 0..4 {
+            assert!(pacer.delay(1000).is_zero());
+            pacer.consume(1000);
+        }
+        let delay = pacer.delay(1000);
+        assert!(delay > Duration::from_millis(900) && delay <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn tiny_rates_are_capped_instead_of_overflowing() {
+        let mut pacer = Pacer::new(1000);
+        pacer.set_rate(Some(1e-300));
+        pacer.consume(5000);
+        assert_eq!(pacer.delay(1000), MAX_DELAY);
+    }
+}
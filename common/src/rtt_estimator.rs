@@ -0,0 +1,145 @@
+use std::cmp::{max, min};
+use std::time::Duration;
+
+/// RTO usado antes da primeira medição de RTT (RFC 6298, seção 2.1).
+const INITIAL_RTO: Duration = Duration::from_secs(1);
+/// Limite inferior do RTO. A RFC recomenda 1s, mas isso é lento demais para redes locais.
+const MIN_RTO: Duration = Duration::from_millis(20);
+/// Limite superior do RTO, inclusive após o backoff exponencial.
+const MAX_RTO: Duration = Duration::from_secs(60);
+/// Granularidade do relógio (o G da RFC 6298): o timeout do `poll` tem resolução de milissegundos e o timer pode ser
+/// tratado com alguns milissegundos de atraso, então o RTO fica sempre ao menos essa margem acima do SRTT.
+const CLOCK_GRANULARITY: Duration = Duration::from_millis(5);
+
+/// Estimador de RTT e do timeout de retransmissão, conforme a RFC 6298.
+///
+/// As amostras devem respeitar o algoritmo de Karn: blocos retransmitidos não devem gerar amostras, já que não é
+/// possível saber a qual transmissão o ack se refere.
+pub struct RttEstimator {
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    rto: Duration,
+}
+
+impl RttEstimator {
+    pub fn new() -> RttEstimator {
+        RttEstimator {
+            srtt: None,
+            rttvar: Duration::from_secs(0),
+            rto: INITIAL_RTO,
+        }
+    }
+
+    /// Atualiza SRTT, RTTVAR e RTO a partir de uma nova medição de RTT.
+    pub fn on_sample(&mut self, rtt: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                // RTTVAR <- (1 - 1/4) * RTTVAR + 1/4 * |SRTT - R'|
+                let delta = srtt.abs_diff(rtt);
+                self.rttvar = (self.rttvar * 3 + delta) / 4;
+                // SRTT <- (1 - 1/8) * SRTT + 1/8 * R'
+                self.srtt = Some((srtt * 7 + rtt) / 8);
+            }
+        }
+
+        let srtt = self.srtt.unwrap();
+        let rto = srtt + max(CLOCK_GRANULARITY, self.rttvar * 4);
+        self.rto = clamp_rto(rto);
+    }
+
+    /// Dobra o RTO após um timeout (backoff exponencial, RFC 6298 seção 5.5).
+    pub fn on_timeout(&mut self) {
+        self.rto = clamp_rto(self.rto * 2);
+    }
+
+    pub fn rto(&self) -> Duration {
+        self.rto
+    }
+
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+}
+
+fn clamp_rto(rto: Duration) -> Duration {
+    min(max(rto, MIN_RTO), MAX_RTO)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{RttEstimator, CLOCK_GRANULARITY, INITIAL_RTO, MAX_RTO, MIN_RTO};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn uses_initial_rto_before_the_first_sample() {
+        let estimator = RttEstimator::new();
+        assert_eq!(estimator.rto(), INITIAL_RTO);
+        assert_eq!(estimator.srtt(), None);
+    }
+
+    #[test]
+    fn first_sample_sets_srtt_and_rttvar() {
+        let mut estimator = RttEstimator::new();
+        estimator.on_sample(ms(100));
+        // SRTT = R, RTTVAR = R/2, RTO = SRTT + 4 * RTTVAR.
+        assert_eq!(estimator.srtt(), Some(ms(100)));
+        assert_eq!(estimator.rto(), ms(300));
+    }
+
+    #[test]
+    fn later_samples_are_smoothed() {
+        let mut estimator = RttEstimator::new();
+        estimator.on_sample(ms(100));
+        estimator.on_sample(ms(200));
+        // RTTVAR = 3/4 * 50 + 1/4 * |100 - 200| = 62,5ms; SRTT = 7/8 * 100 + 1/8 * 200 = 112,5ms.
+        assert_eq!(estimator.srtt(), Some(Duration::from_micros(112_500)));
+        assert_eq!(estimator.rto(), Duration::from_micros(112_500 + 4 * 62_500));
+    }
+
+    #[test]
+    fn stable_samples_keep_the_rto_above_srtt_by_the_clock_granularity() {
+        let mut estimator = RttEstimator::new();
+        for _ in 0..100 {
+            estimator.on_sample(ms(30));
+        }
+        assert_eq!(estimator.rto(), ms(30) + CLOCK_GRANULARITY);
+    }
+
+    #[test]
+    fn rto_is_clamped() {
+        let mut estimator = RttEstimator::new();
+        estimator.on_sample(ms(1));
+        assert_eq!(estimator.rto(), MIN_RTO);
+
+        estimator.on_sample(Duration::from_secs(100));
+        assert_eq!(estimator.rto(), MAX_RTO);
+    }
+
+    #[test]
+    fn timeouts_double_the_rto_up_to_the_limit() {
+        let mut estimator = RttEstimator::new();
+        estimator.on_sample(ms(100));
+        estimator.on_timeout();
+        assert_eq!(estimator.rto(), ms(600));
+        estimator.on_timeout();
+        assert_eq!(estimator.rto(), ms(1200));
+
+        for _ in 0..20 {
+            estimator.on_timeout();
+        }
+        assert_eq!(estimator.rto(), MAX_RTO);
+
+        // Uma nova amostra recalcula o RTO a partir de SRTT e RTTVAR, desfazendo o backoff.
+        estimator.on_sample(ms(100));
+        assert!(estimator.rto() < ms(600));
+    }
+}
@@ -0,0 +1,832 @@
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, trace, warn};
+
+use crate::congestion_control::{AckEvent, CongestionAlgorithm, CongestionController};
+use crate::pacer::Pacer;
+use crate::rtt_estimator::RttEstimator;
+use crate::{
+    AckData, ChunkData, ConnectionData, Message, NoopObserver, TransferObserver, TransferStats,
+    CHUNK_SIZE, FILE_HEADER_SIZE, MAX_CHUNK_SIZE,
+};
+
+/// Quantidade de acks duplicados que dispara a retransmissão rápida.
+const DUPLICATE_ACK_THRESHOLD: u32 = 3;
+
+/// Opções do envio de um arquivo.
+#[derive(Clone, Copy, Debug)]
+pub struct SendOptions {
+    pub congestion_algorithm: CongestionAlgorithm,
+    /// Taxa máxima de envio, em bytes por segundo.
+    pub max_rate: Option<f64>,
+    /// Tamanho, em bytes, do conteúdo de cada bloco, entre 1 e `MAX_CHUNK_SIZE`.
+    pub chunk_size: usize,
+    /// Limite de blocos em trânsito, além dos impostos pelo controle de congestionamento e pelo servidor.
+    pub window: Option<u16>,
+    /// Tempo máximo sem resposta do servidor antes de desistir da transferência. No download, o cliente usa um prazo
+    /// padrão se ele não for definido.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for SendOptions {
+    fn default() -> SendOptions {
+        SendOptions {
+            congestion_algorithm: CongestionAlgorithm::NewReno,
+            max_rate: None,
+            chunk_size: CHUNK_SIZE,
+            window: None,
+            timeout: None,
+        }
+    }
+}
+
+impl SendOptions {
+    /// Verifica se as opções podem ser usadas num envio.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.chunk_size == 0 || self.chunk_size > MAX_CHUNK_SIZE {
+            return Err(format!(
+                "O tamanho do bloco deve estar entre 1 e {} bytes",
+                MAX_CHUNK_SIZE
+            ));
+        }
+        if self.window == Some(0) {
+            return Err("A janela de envio deve ter ao menos um bloco".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Conteúdo do arquivo enviado.
+enum Contents {
+    /// O arquivo inteiro, carregado antes do envio.
+    Whole(Vec<u8>),
+    /// Só os blocos a partir do primeiro não confirmado, entregues aos poucos por `Sender::push_chunks`.
+    Streamed(VecDeque<Vec<u8>>),
+}
+
+/// Um bloco enviado e ainda não confirmado.
+struct SentChunk {
+    /// Instante do último envio do bloco.
+    sent_at: Instant,
+    /// Se o bloco já foi retransmitido; nesse caso, o seu ack não gera amostra de RTT (algoritmo de Karn).
+    retransmitted: bool,
+}
+
+/// Estado do envio do arquivo via UDP com janela deslizante (go-back-N).
+///
+/// Não faz nenhuma espera nem I/O direto: o laço de eventos chama `send_ready_chunks` quando o socket pode ser
+/// escrito, `on_ack` para cada ack recebido e `on_timer` quando o prazo de `next_deadline` é atingido. Os datagramas
+/// são entregues à função `transmit`, que deve retornar um erro de `WouldBlock` caso o socket não aceite mais
+/// datagramas no momento.
+pub struct Sender {
+    contents: Contents,
+    file_size: u64,
+    /// Sessão e token informados pelo servidor na mensagem "Connection", enviados em cada bloco.
+    session_id: u32,
+    token: u64,
+    chunk_size: usize,
+    chunk_count: u32,
+
+    /// Primeiro bloco ainda não confirmado.
+    send_base: u32,
+    /// Próximo bloco a ser enviado por `send_ready_chunks`. Depois de um timeout, volta para logo após `send_base`, e
+    /// os blocos até `sent_until` são retransmitidos conforme a janela de congestionamento permite.
+    next_sequence_number: u32,
+    /// Blocos de `send_base` a `sent_until`, exclusive, já foram enviados ao menos uma vez.
+    sent_until: u32,
+    highest_ack: Option<u32>,
+    duplicate_acks: u32,
+    receive_window: u16,
+    max_window: Option<u16>,
+
+    /// Blocos enviados e ainda não confirmados, de `send_base` a `sent_until`, exclusive.
+    in_flight: VecDeque<SentChunk>,
+
+    timer_started_at: Instant,
+    rtt_estimator: RttEstimator,
+    congestion_controller: Box<dyn CongestionController>,
+    pacer: Pacer,
+    max_rate: Option<f64>,
+    /// Instante a partir do qual o pacer permite o próximo envio, caso esteja aguardando.
+    next_send_at: Option<Instant>,
+    timeout: Option<Duration>,
+    /// Instante do último ack recebido, ou do início do envio.
+    last_ack_at: Instant,
+    /// Início da pausa atual, durante a qual nenhum bloco é enviado e os prazos não correm.
+    paused_at: Option<Instant>,
+    observer: Box<dyn TransferObserver>,
+
+    started_at: Instant,
+    completed_at: Option<Instant>,
+    stats: TransferStats,
+    /// Soma das amostras de RTT, para o cálculo da média.
+    total_rtt: Duration,
+    rtt_samples: u32,
+}
+
+impl Sender {
+    /// Cria o emissor de `file_contents` para a sessão descrita em `connection`, a resposta do servidor ao "Hello".
+    pub fn new(
+        file_contents: Vec<u8>,
+        connection: &ConnectionData,
+        options: &SendOptions,
+    ) -> Sender {
+        let file_size = file_contents.len() as u64;
+        Sender::with_contents(
+            Contents::Whole(file_contents),
+            file_size,
+            connection,
+            options,
+        )
+    }
+
+    /// Cria o emissor de um arquivo de `file_size` bytes cujos blocos são entregues aos poucos, em ordem, por
+    /// `push_chunks`. Só os blocos ainda não confirmados ficam em memória, e o envio espera pelos que não foram
+    /// entregues.
+    pub fn streamed(file_size: u64, connection: &ConnectionData, options: &SendOptions) -> Sender {
+        Sender::with_contents(
+            Contents::Streamed(VecDeque::new()),
+            file_size,
+            connection,
+            options,
+        )
+    }
+
+    fn with_contents(
+        contents: Contents,
+        file_size: u64,
+        connection: &ConnectionData,
+        options: &SendOptions,
+    ) -> Sender {
+        // O servidor espera tamanho / tamanho do bloco + 1 blocos, então o último bloco pode ser vazio.
+        let chunk_size = options.chunk_size;
+        let chunk_count = (file_size / chunk_size as u64) as u32 + 1;
+
+        Sender {
+            contents,
+            file_size,
+            session_id: connection.session_id,
+            token: connection.token,
+            chunk_size,
+            chunk_count,
+            send_base: 0,
+            next_sequence_number: 0,
+            sent_until: 0,
+            highest_ack: None,
+            duplicate_acks: 0,
+            receive_window: connection.receive_window,
+            max_window: options.window,
+            in_flight: VecDeque::new(),
+            timer_started_at: Instant::now(),
+            rtt_estimator: RttEstimator::new(),
+            congestion_controller: options.congestion_algorithm.build(),
+            pacer: Pacer::new(chunk_size + FILE_HEADER_SIZE),
+            max_rate: options.max_rate,
+            next_send_at: None,
+            timeout: options.timeout,
+            last_ack_at: Instant::now(),
+            paused_at: None,
+            observer: Box::new(NoopObserver),
+            started_at: Instant::now(),
+            completed_at: None,
+            stats: TransferStats::default(),
+            total_rtt: Duration::from_secs(0),
+            rtt_samples: 0,
+        }
+    }
+
+    /// Define o observador notificado dos acks e das retransmissões.
+    pub fn with_observer(mut self, observer: Box<dyn TransferObserver>) -> Sender {
+        self.observer = observer;
+        self
+    }
+
+    /// O observador da transferência, para que o laço de eventos notifique o fim ou os erros do envio.
+    pub fn observer(&mut self) -> &mut dyn TransferObserver {
+        self.observer.as_mut()
+    }
+
+    /// Devolve o observador, para que seja reaproveitado na próxima transferência.
+    pub fn into_observer(self) -> Box<dyn TransferObserver> {
+        self.observer
+    }
+
+    /// Estatísticas do envio até o momento. O tempo é contado da criação do `Sender` até a confirmação do último
+    /// bloco, sem as pausas.
+    pub fn stats(&self) -> TransferStats {
+        let mut stats = self.stats.clone();
+        stats.file_size = self.file_size;
+        stats.elapsed = self.completed_at.unwrap_or_else(Instant::now) - self.started_at;
+        if self.rtt_samples > 0 {
+            stats.avg_rtt = Some(self.total_rtt / self.rtt_samples);
+        }
+        stats
+    }
+
+    /// Suspende o envio: até `resume`, nenhum bloco é enviado ou retransmitido e `next_deadline` não tem prazos. Os
+    /// acks dos blocos já em trânsito continuam sendo processados.
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(Instant::now());
+        }
+    }
+
+    /// Retoma o envio depois de `pause`, com a mesma janela. Os temporizadores são adiados pela duração da pausa,
+    /// para que ela não seja interpretada como perda de blocos nem como falta de resposta do servidor.
+    pub fn resume(&mut self) {
+        let paused_for = match self.paused_at.take() {
+            Some(paused_at) => paused_at.elapsed(),
+            None => return,
+        };
+
+        self.timer_started_at += paused_for;
+        self.last_ack_at += paused_for;
+        self.started_at += paused_for;
+        for sent_chunk in &mut self.in_flight {
+            sent_chunk.sent_at += paused_for;
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Todos os blocos foram confirmados.
+    pub fn is_complete(&self) -> bool {
+        self.send_base >= self.chunk_count
+    }
+
+    /// Quantidade de blocos do arquivo.
+    pub fn chunk_count(&self) -> u32 {
+        self.chunk_count
+    }
+
+    /// Primeiro bloco ainda não confirmado. No envio com `streamed`, os blocos anteriores já foram descartados.
+    pub fn send_base(&self) -> u32 {
+        self.send_base
+    }
+
+    /// Entrega os próximos blocos do arquivo, na ordem, a um emissor criado com `streamed`. O envio continua na
+    /// próxima chamada de `send_ready_chunks`.
+    pub fn push_chunks(&mut self, chunks: Vec<Vec<u8>>) {
+        if let Contents::Streamed(loaded) = &mut self.contents {
+            loaded.extend(chunks);
+        }
+    }
+
+    /// Envia todos os blocos permitidos pela janela e pelo pacer.
+    pub fn send_ready_chunks<F>(&mut self, mut transmit: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        self.next_send_at = None;
+        if self.is_paused() {
+            return Ok(());
+        }
+
+        loop {
+            self.pacer.set_rate(pacing_rate(
+                self.congestion_controller.as_ref(),
+                self.chunk_size,
+                self.max_rate,
+            ));
+
+            // A janela efetiva nunca excede a janela anunciada pelo servidor nem o limite configurado.
+            let window_size = min(
+                self.congestion_controller.cwnd(),
+                self.receive_window as u32,
+            );
+            let window_size = self.max_window.map_or(window_size, |max_window| {
+                min(window_size, max_window as u32)
+            });
+            let can_send = self.next_sequence_number < self.chunk_count
+                && self.next_sequence_number < self.send_base + window_size;
+            if !can_send {
+                return Ok(());
+            }
+
+            let datagram_size = match self.chunk(self.next_sequence_number) {
+                Some(chunk) => chunk.len() + FILE_HEADER_SIZE,
+                // O bloco ainda não foi entregue por `push_chunks`.
+                None => return Ok(()),
+            };
+            let delay = self.pacer.delay(datagram_size);
+            if !delay.is_zero() {
+                self.next_send_at = Some(Instant::now() + delay);
+                return Ok(());
+            }
+
+            if self.send_base == self.next_sequence_number {
+                // Não havia blocos em trânsito, então o temporizador de retransmissão começa agora.
+                self.timer_started_at = Instant::now();
+            }
+            if self.next_sequence_number < self.sent_until {
+                // Bloco enviado antes de um timeout, e retransmitido agora que a janela permite.
+                self.observer.on_retransmit(self.next_sequence_number);
+                self.stats.retransmissions += 1;
+            }
+            self.send_chunk(self.next_sequence_number, &mut transmit)?;
+            self.pacer.consume(datagram_size);
+            self.next_sequence_number += 1;
+        }
+    }
+
+    /// Processa um ack recebido do servidor.
+    pub fn on_ack<F>(&mut self, ack: AckData, mut transmit: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        let AckData {
+            sequence_number: num,
+            receive_window: advertised_window,
+        } = ack;
+        self.stats.acks += 1;
+        self.last_ack_at = Instant::now();
+
+        if advertised_window != self.receive_window {
+            debug!(
+                receive_window = advertised_window,
+                "Janela de recepção do servidor alterada"
+            );
+            self.receive_window = advertised_window;
+        }
+
+        if num >= self.chunk_count || num >= self.sent_until {
+            warn!(chunk = num, "Ack para bloco inexistente ignorado");
+            return Ok(());
+        }
+
+        let is_new_ack = self.highest_ack.is_none_or(|highest| num > highest);
+        if is_new_ack {
+            let acked_chunks = match self.highest_ack {
+                Some(highest) => num - highest,
+                None => num + 1,
+            };
+            self.highest_ack = Some(num);
+            self.duplicate_acks = 0;
+
+            // Amostras só são coletadas de blocos que não foram retransmitidos.
+            let rtt = match self.sent_chunk(num) {
+                Some(sent_chunk) if !sent_chunk.retransmitted => Some(sent_chunk.sent_at.elapsed()),
+                _ => None,
+            };
+            if let Some(rtt) = rtt {
+                self.rtt_estimator.on_sample(rtt);
+                self.record_rtt(rtt);
+                trace!(
+                    chunk = num,
+                    srtt = ?self.rtt_estimator.srtt().unwrap(),
+                    rto = ?self.rtt_estimator.rto(),
+                    "RTT medido"
+                );
+            }
+
+            self.congestion_controller.on_ack(&AckEvent {
+                now: Instant::now(),
+                acked_chunks,
+                rtt,
+            });
+            self.log_congestion_window("ack");
+            self.timer_started_at = Instant::now();
+            let newly_acked = (num + 1 - self.send_base) as usize;
+            self.in_flight
+                .drain(..newly_acked.min(self.in_flight.len()));
+            if let Contents::Streamed(loaded) = &mut self.contents {
+                loaded.drain(..newly_acked.min(loaded.len()));
+            }
+            self.send_base = num + 1;
+            // Um ack de um bloco enviado antes do timeout dispensa a retransmissão dos blocos até ele.
+            self.next_sequence_number = max(self.next_sequence_number, self.send_base);
+            if self.is_complete() {
+                self.completed_at = Some(Instant::now());
+            }
+
+            let acked_bytes = min(
+                self.send_base as u64 * self.chunk_size as u64,
+                self.file_size,
+            );
+            self.observer.on_chunk_acked(num, acked_bytes);
+        } else {
+            self.duplicate_acks += 1;
+            let missing_chunk = num + 1;
+            if self.duplicate_acks == DUPLICATE_ACK_THRESHOLD
+                && missing_chunk < self.next_sequence_number
+                && !self.is_paused()
+            {
+                // Retransmissão rápida do primeiro bloco que o servidor ainda não recebeu.
+                self.congestion_controller.on_loss(
+                    Instant::now(),
+                    missing_chunk,
+                    self.sent_until - 1,
+                );
+                self.log_congestion_window("acks duplicados");
+                if let Some(sent_chunk) = self.sent_chunk_mut(missing_chunk) {
+                    sent_chunk.retransmitted = true;
+                }
+                self.observer.on_retransmit(missing_chunk);
+                self.stats.retransmissions += 1;
+                self.send_chunk(missing_chunk, &mut transmit)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Retransmite o primeiro bloco não confirmado caso o temporizador de retransmissão tenha expirado (RFC 5681,
+    /// seção 3.1); os demais são retransmitidos por `send_ready_chunks` à medida que a janela de congestionamento
+    /// volta a crescer. Retorna um erro de `TimedOut` caso o servidor não tenha respondido dentro do `timeout`
+    /// configurado.
+    pub fn on_timer<F>(&mut self, mut transmit: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        if self.is_paused() {
+            return Ok(());
+        }
+
+        if let Some(deadline) = self.timeout_deadline() {
+            if Instant::now() >= deadline {
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    format!(
+                        "O servidor não respondeu em {:?}",
+                        self.timeout.unwrap_or_default()
+                    ),
+                ));
+            }
+        }
+
+        let timed_out = self.has_chunks_in_flight()
+            && self.timer_started_at.elapsed() >= self.rtt_estimator.rto();
+        if !timed_out {
+            return Ok(());
+        }
+
+        self.rtt_estimator.on_timeout();
+        self.congestion_controller.on_timeout(Instant::now());
+        self.log_congestion_window("timeout");
+        debug!(
+            chunk = self.send_base,
+            rto = ?self.rtt_estimator.rto(),
+            "Timeout, retransmitindo bloco"
+        );
+
+        self.timer_started_at = Instant::now();
+        for sent_chunk in &mut self.in_flight {
+            sent_chunk.retransmitted = true;
+        }
+        self.duplicate_acks = 0;
+        self.next_sequence_number = self.send_base + 1;
+        self.observer.on_retransmit(self.send_base);
+        self.stats.retransmissions += 1;
+        self.send_chunk(self.send_base, &mut transmit)?;
+
+        Ok(())
+    }
+
+    /// Próximo instante em que o laço de eventos deve acordar, mesmo sem eventos nos sockets: o envio permitido pelo
+    /// pacer, a expiração do temporizador de retransmissão ou o fim do prazo de resposta do servidor.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        if self.is_paused() {
+            return None;
+        }
+
+        let retransmission_deadline = if self.has_chunks_in_flight() {
+            Some(self.timer_started_at + self.rtt_estimator.rto())
+        } else {
+            None
+        };
+
+        [
+            self.next_send_at,
+            retransmission_deadline,
+            self.timeout_deadline(),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+    }
+
+    fn timeout_deadline(&self) -> Option<Instant> {
+        match self.timeout {
+            Some(timeout) if !self.is_complete() => Some(self.last_ack_at + timeout),
+            _ => None,
+        }
+    }
+
+    fn has_chunks_in_flight(&self) -> bool {
+        self.send_base < self.next_sequence_number
+    }
+
+    /// Conteúdo do bloco `index`, caso esteja em memória.
+    fn chunk(&self, index: u32) -> Option<&[u8]> {
+        match &self.contents {
+            Contents::Whole(file_contents) => {
+                let start = min(index as usize * self.chunk_size, file_contents.len());
+                let end = min(start + self.chunk_size, file_contents.len());
+                Some(&file_contents[start..end])
+            }
+            Contents::Streamed(loaded) => index
+                .checked_sub(self.send_base)
+                .and_then(|offset| loaded.get(offset as usize))
+                .map(Vec::as_slice),
+        }
+    }
+
+    fn sent_chunk(&self, index: u32) -> Option<&SentChunk> {
+        let offset = index.checked_sub(self.send_base)?;
+        self.in_flight.get(offset as usize)
+    }
+
+    fn sent_chunk_mut(&mut self, index: u32) -> Option<&mut SentChunk> {
+        let offset = index.checked_sub(self.send_base)?;
+        self.in_flight.get_mut(offset as usize)
+    }
+
+    fn send_chunk<F>(&mut self, index: u32, transmit: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        let chunk = self
+            .chunk(index)
+            .ok_or_else(|| Error::other(format!("Bloco {} não está em memória", index)))?;
+        let data = Message::File(ChunkData {
+            session_id: self.session_id,
+            token: self.token,
+            sequence_number: index,
+            payload_size: chunk.len() as u16,
+            data: chunk.to_vec(),
+        })
+        .encode();
+
+        transmit(&data)?;
+        let sent_at = Instant::now();
+        match self.sent_chunk_mut(index) {
+            Some(sent_chunk) => sent_chunk.sent_at = sent_at,
+            None => {
+                self.in_flight.push_back(SentChunk {
+                    sent_at,
+                    retransmitted: false,
+                });
+                self.sent_until = index + 1;
+            }
+        }
+        self.stats.chunks_sent += 1;
+
+        Ok(())
+    }
+
+    fn record_rtt(&mut self, rtt: Duration) {
+        self.stats.min_rtt = Some(self.stats.min_rtt.map_or(rtt, |min_rtt| min(min_rtt, rtt)));
+        self.stats.max_rtt = Some(self.stats.max_rtt.map_or(rtt, |max_rtt| max(max_rtt, rtt)));
+        self.total_rtt += rtt;
+        self.rtt_samples += 1;
+    }
+
+    fn log_congestion_window(&self, event: &str) {
+        trace!(
+            event,
+            algorithm = self.congestion_controller.name(),
+            cwnd = self.congestion_controller.cwnd(),
+            pacing_rate = ?self.congestion_controller.pacing_rate(),
+            "Janela de congestionamento"
+        );
+    }
+}
+
+/// Taxa de envio, em bytes por segundo: a sugerida pelo controle de congestionamento, limitada por `max_rate`.
+fn pacing_rate(
+    congestion_controller: &dyn CongestionController,
+    chunk_size: usize,
+    max_rate: Option<f64>,
+) -> Option<f64> {
+    let suggested_rate = congestion_controller
+        .pacing_rate()
+        .map(|chunks_per_second| chunks_per_second * chunk_size as f64);
+    match (suggested_rate, max_rate) {
+        (Some(rate), Some(max_rate)) => Some(rate.min(max_rate)),
+        (rate, None) => rate,
+        (None, max_rate) => max_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::{SendOptions, Sender};
+    use crate::congestion_control::{AckEvent, CongestionController, NewReno};
+    use crate::{AckData, ChunkData, ConnectionData, FileReceiver, Message};
+
+    const CHUNK_SIZE: usize = 4;
+    const RECEIVE_WINDOW: u16 = 64;
+
+    /// Emissor de um arquivo de `chunks` blocos cheios, seguidos do bloco vazio final.
+    fn sender(chunks: usize) -> Sender {
+        let connection = ConnectionData {
+            port: 0,
+            receive_window: RECEIVE_WINDOW,
+            session_id: 1,
+            token: 0,
+        };
+        let options = SendOptions {
+            chunk_size: CHUNK_SIZE,
+            ..SendOptions::default()
+        };
+        Sender::new(vec![7; chunks * CHUNK_SIZE], &connection, &options)
+    }
+
+    /// NewReno que conta as perdas informadas pelo emissor.
+    struct CountingLosses {
+        inner: NewReno,
+        losses: Arc<AtomicU32>,
+    }
+
+    impl CongestionController for CountingLosses {
+        fn on_ack(&mut self, event: &AckEvent) {
+            self.inner.on_ack(event);
+        }
+
+        fn on_loss(&mut self, now: Instant, lost_chunk: u32, highest_sent: u32) {
+            self.losses.fetch_add(1, Ordering::SeqCst);
+            self.inner.on_loss(now, lost_chunk, highest_sent);
+        }
+
+        fn on_timeout(&mut self, now: Instant) {
+            self.inner.on_timeout(now);
+        }
+
+        fn cwnd(&self) -> u32 {
+            self.inner.cwnd()
+        }
+
+        fn pacing_rate(&self) -> Option<f64> {
+            self.inner.pacing_rate()
+        }
+
+        fn name(&self) -> &'static str {
+            self.inner.name()
+        }
+    }
+
+    /// Janela que cresce um bloco por bloco confirmado e volta a um bloco no timeout, sem pacer.
+    struct SlowStart {
+        cwnd: u32,
+    }
+
+    impl CongestionController for SlowStart {
+        fn on_ack(&mut self, event: &AckEvent) {
+            self.cwnd += event.acked_chunks;
+        }
+
+        fn on_loss(&mut self, _now: Instant, _lost_chunk: u32, _highest_sent: u32) {
+            self.cwnd = (self.cwnd / 2).max(1);
+        }
+
+        fn on_timeout(&mut self, _now: Instant) {
+            self.cwnd = 1;
+        }
+
+        fn cwnd(&self) -> u32 {
+            self.cwnd
+        }
+
+        fn pacing_rate(&self) -> Option<f64> {
+            None
+        }
+
+        fn name(&self) -> &'static str {
+            "slow start"
+        }
+    }
+
+    fn parse_chunk(datagram: &[u8]) -> ChunkData {
+        match Message::new(datagram, datagram.len()).unwrap() {
+            Message::File(chunk) => chunk,
+            _ => panic!("Esperava uma mensagem \"File\""),
+        }
+    }
+
+    fn ack(sequence_number: u32) -> AckData {
+        AckData {
+            sequence_number,
+            receive_window: RECEIVE_WINDOW,
+        }
+    }
+
+    #[test]
+    fn duplicate_acks_retransmit_a_lost_chunk_before_the_rto() {
+        let losses = Arc::new(AtomicU32::new(0));
+        let mut sender = sender(40);
+        sender.congestion_controller = Box::new(CountingLosses {
+            inner: NewReno::new(),
+            losses: Arc::clone(&losses),
+        });
+        let mut receiver =
+            FileReceiver::new(40 * CHUNK_SIZE as u64, CHUNK_SIZE as u16, RECEIVE_WINDOW);
+        let mut network = VecDeque::new();
+        let mut lost = false;
+        let mut transmissions_of_lost_chunk = 0;
+
+        // `on_timer` nunca é chamado: a transferência só termina se a perda for recuperada pelos acks duplicados.
+        while !sender.is_complete() {
+            sender
+                .send_ready_chunks(|datagram| {
+                    network.push_back(datagram.to_vec());
+                    Ok(())
+                })
+                .unwrap();
+
+            let datagram = match network.pop_front() {
+                Some(datagram) => datagram,
+                None => {
+                    assert!(
+                        sender.next_send_at.is_some(),
+                        "Envio parado à espera do RTO"
+                    );
+                    std::thread::yield_now();
+                    continue;
+                }
+            };
+            let chunk = parse_chunk(&datagram);
+            if chunk.sequence_number == 5 {
+                transmissions_of_lost_chunk += 1;
+                if !lost {
+                    lost = true;
+                    continue;
+                }
+            }
+            for sequence_number in receiver.on_chunk(chunk.sequence_number, chunk.data).acks {
+                sender
+                    .on_ack(ack(sequence_number), |datagram| {
+                        network.push_back(datagram.to_vec());
+                        Ok(())
+                    })
+                    .unwrap();
+            }
+        }
+
+        assert_eq!(transmissions_of_lost_chunk, 2);
+        assert_eq!(sender.stats().retransmissions, 1);
+        assert_eq!(losses.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn timeout_retransmits_only_the_first_unacked_chunk() {
+        let mut sender = sender(40);
+        sender.congestion_controller = Box::new(SlowStart { cwnd: 6 });
+        let sent = RefCell::new(Vec::new());
+        let transmit = |datagram: &[u8]| {
+            sent.borrow_mut()
+                .push(parse_chunk(datagram).sequence_number);
+            Ok(())
+        };
+
+        sender.send_ready_chunks(&transmit).unwrap();
+        sender.on_ack(ack(1), &transmit).unwrap();
+        sender.send_ready_chunks(&transmit).unwrap();
+        assert_eq!(sent.take(), (0..10).collect::<Vec<_>>());
+
+        // Oito blocos em trânsito, mas o timeout só retransmite o primeiro.
+        sender.timer_started_at -= Duration::from_secs(2);
+        sender.on_timer(&transmit).unwrap();
+        assert_eq!(sent.take(), vec![2]);
+        sender.send_ready_chunks(&transmit).unwrap();
+        assert!(sent.take().is_empty());
+
+        // Os demais são retransmitidos à medida que a janela cresce.
+        sender.on_ack(ack(2), &transmit).unwrap();
+        sender.send_ready_chunks(&transmit).unwrap();
+        assert_eq!(sent.take(), vec![3, 4]);
+        assert_eq!(sender.stats().retransmissions, 3);
+
+        // Um ack de um bloco enviado antes do timeout dispensa a retransmissão dos anteriores a ele.
+        sender.on_ack(ack(9), &transmit).unwrap();
+        sender.send_ready_chunks(&transmit).unwrap();
+        assert_eq!(sent.take(), (10..19).collect::<Vec<_>>());
+        assert_eq!(sender.stats().retransmissions, 3);
+    }
+
+    #[test]
+    fn validate_reports_the_max_chunk_size() {
+        let options = |chunk_size| SendOptions {
+            chunk_size,
+            ..SendOptions::default()
+        };
+
+        assert!(options(crate::MAX_CHUNK_SIZE).validate().is_ok());
+        assert_eq!(
+            options(crate::MAX_CHUNK_SIZE + 1).validate(),
+            Err("O tamanho do bloco deve estar entre 1 e 65487 bytes".to_string())
+        );
+        assert!(options(0).validate().is_err());
+    }
+}
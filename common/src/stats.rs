@@ -0,0 +1,98 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Contadores de uma transferência, coletados pelo lado que envia o arquivo (`Sender`) ou pelo que o recebe, para
+/// comparar a qualidade do enlace entre diferentes locais.
+///
+/// Os contadores que não se aplicam ao lado que os coletou ficam zerados: quem envia não recebe blocos, e quem recebe
+/// não envia blocos nem mede o RTT.
+#[derive(Clone, Debug, Default)]
+pub struct TransferStats {
+    pub file_size: u64,
+    /// Datagramas de dados enviados, incluindo as retransmissões.
+    pub chunks_sent: u64,
+    pub retransmissions: u64,
+    /// Datagramas de dados recebidos, incluindo duplicados e descartados.
+    pub chunks_received: u64,
+    /// Blocos recebidos mais de uma vez.
+    pub duplicates_received: u64,
+    /// Blocos descartados por estarem além da janela de recepção ou fora do arquivo.
+    pub out_of_window_drops: u64,
+    pub acks: u64,
+    pub min_rtt: Option<Duration>,
+    pub avg_rtt: Option<Duration>,
+    pub max_rtt: Option<Duration>,
+    pub elapsed: Duration,
+}
+
+impl TransferStats {
+    /// Bytes úteis do arquivo transferidos por segundo.
+    pub fn goodput(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.file_size as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Representação em JSON, numa única linha. Durações são informadas em milissegundos e o goodput em bytes por
+    /// segundo; RTTs não medidos são `null`.
+    pub fn to_json(&self) -> String {
+        format!(
+            concat!(
+                "{{\"file_size\":{},\"chunks_sent\":{},\"retransmissions\":{},\"chunks_received\":{},",
+                "\"duplicates_received\":{},\"out_of_window_drops\":{},\"acks\":{},\"min_rtt_ms\":{},",
+                "\"avg_rtt_ms\":{},\"max_rtt_ms\":{},\"elapsed_ms\":{},\"goodput_bytes_per_sec\":{:.0}}}"
+            ),
+            self.file_size,
+            self.chunks_sent,
+            self.retransmissions,
+            self.chunks_received,
+            self.duplicates_received,
+            self.out_of_window_drops,
+            self.acks,
+            json_millis(self.min_rtt),
+            json_millis(self.avg_rtt),
+            json_millis(self.max_rtt),
+            json_millis(Some(self.elapsed)),
+            self.goodput()
+        )
+    }
+}
+
+impl fmt::Display for TransferStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Estatísticas da transferência:")?;
+        writeln!(f, "  tamanho do arquivo:   {} bytes", self.file_size)?;
+        writeln!(f, "  tempo:                {:.3} s", self.elapsed.as_secs_f64())?;
+        writeln!(f, "  goodput:              {:.1} kB/s", self.goodput() / 1000.0)?;
+        if self.chunks_sent > 0 {
+            writeln!(
+                f,
+                "  blocos enviados:      {} ({} retransmissões)",
+                self.chunks_sent, self.retransmissions
+            )?;
+        }
+        if self.chunks_received > 0 {
+            writeln!(
+                f,
+                "  blocos recebidos:     {} ({} duplicados, {} fora da janela)",
+                self.chunks_received, self.duplicates_received, self.out_of_window_drops
+            )?;
+        }
+        writeln!(f, "  acks:                 {}", self.acks)?;
+        if let (Some(min), Some(avg), Some(max)) = (self.min_rtt, self.avg_rtt, self.max_rtt) {
+            writeln!(f, "  RTT mín/méd/máx:      {:?} / {:?} / {:?}", min, avg, max)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn json_millis(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("{:.3}", duration.as_secs_f64() * 1000.0),
+        None => String::from("null"),
+    }
+}
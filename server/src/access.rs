@@ -0,0 +1,78 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Faixa de endereços IP no formato CIDR (por exemplo, "10.0.0.0/8" ou "2001:db8::/32"), usada para restringir os
+/// clientes aceitos pelo servidor. Um endereço sem prefixo representa apenas ele mesmo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix_length: u8,
+}
+
+impl IpNetwork {
+    /// Se `address` pertence à faixa. Endereços IPv4 mapeados em IPv6 (como os dos clientes IPv4 de um socket em
+    /// `[::]`) são comparados como IPv4.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                prefix_matches(&network.octets(), &address.octets(), self.prefix_length)
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                prefix_matches(&network.octets(), &address.octets(), self.prefix_length)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(network: &[u8], address: &[u8], prefix_length: u8) -> bool {
+    let full_bytes = prefix_length as usize / 8;
+    let remaining_bits = prefix_length % 8;
+
+    if network[..full_bytes] != address[..full_bytes] {
+        return false;
+    }
+    if remaining_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - remaining_bits);
+    network[full_bytes] & mask == address[full_bytes] & mask
+}
+
+impl FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(network: &str) -> Result<IpNetwork, String> {
+        let invalid = || format!("Faixa de endereços inválida: \"{}\"", network);
+
+        let (address, prefix_length) = match network.split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length)),
+            None => (network, None),
+        };
+        let address = address
+            .trim()
+            .parse::<IpAddr>()
+            .map_err(|_e| invalid())?
+            .to_canonical();
+        let max_prefix_length = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => match prefix_length.trim().parse::<u8>() {
+                Ok(prefix_length) if prefix_length <= max_prefix_length => prefix_length,
+                _ => return Err(invalid()),
+            },
+            None => max_prefix_length,
+        };
+
+        Ok(IpNetwork {
+            address,
+            prefix_length,
+        })
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_length)
+    }
+}
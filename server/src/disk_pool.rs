@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::fs::{self, create_dir_all, File};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use mio::Waker;
+use tracing::error;
+
+/// Operações de disco de uma sessão.
+enum DiskJob {
+    Create {
+        session: usize,
+        path: PathBuf,
+        pending: Arc<AtomicUsize>,
+    },
+    Write {
+        session: usize,
+        data: Vec<u8>,
+    },
+    Finish {
+        session: usize,
+    },
+    Close {
+        session: usize,
+    },
+    Discard {
+        session: usize,
+    },
+    Open {
+        session: usize,
+        path: PathBuf,
+    },
+    Read {
+        session: usize,
+        offset: u64,
+        chunk_size: usize,
+        count: usize,
+    },
+}
+
+/// Arquivo de saída de uma sessão, aberto por uma das threads de escrita.
+struct OpenFile {
+    file: File,
+    path: PathBuf,
+    /// Blocos da sessão enfileirados e ainda não escritos.
+    pending: Arc<AtomicUsize>,
+}
+
+/// Resultados das operações de disco, entregues ao laço de eventos.
+pub enum DiskEvent {
+    /// Todos os blocos da sessão foram escritos e o arquivo foi fechado.
+    Finished { session: usize },
+    /// O arquivo pedido pela sessão foi aberto para leitura.
+    Opened { session: usize, file_size: u64 },
+    /// Os próximos blocos do arquivo pedido pela sessão foram lidos.
+    ChunksRead {
+        session: usize,
+        chunks: Vec<Vec<u8>>,
+    },
+    /// Uma operação de disco da sessão falhou; as próximas operações da sessão são ignoradas.
+    Failed { session: usize, error: Error },
+}
+
+/// Conjunto limitado de threads que fazem a escrita e a leitura dos arquivos, para que o laço de eventos nunca espere
+/// pelo disco.
+///
+/// Cada sessão é sempre atendida pela mesma thread, o que garante que os blocos sejam escritos, ou lidos, na ordem em
+/// que foram enfileirados. A quantidade de blocos pendentes de cada sessão é usada para calcular a janela anunciada ao cliente.
+pub struct DiskPool {
+    workers: Vec<Sender<DiskJob>>,
+    threads: Vec<JoinHandle<()>>,
+    events: Arc<Mutex<Vec<DiskEvent>>>,
+}
+
+impl DiskPool {
+    pub fn new(worker_count: usize, waker: Arc<Waker>) -> DiskPool {
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        let (workers, threads) = (0..worker_count.max(1))
+            .map(|_| {
+                let (sender, receiver) = mpsc::channel();
+                let events = Arc::clone(&events);
+                let waker = Arc::clone(&waker);
+                let thread = thread::spawn(move || run_worker(receiver, events, waker));
+                (sender, thread)
+            })
+            .unzip();
+
+        DiskPool {
+            workers,
+            threads,
+            events,
+        }
+    }
+
+    /// Cria o arquivo de saída da sessão, e o diretório que o contém caso necessário. `pending` passa a contar os
+    /// blocos enfileirados e ainda não escritos.
+    pub fn create(&self, session: usize, path: PathBuf, pending: Arc<AtomicUsize>) {
+        self.submit(
+            session,
+            DiskJob::Create {
+                session,
+                path,
+                pending,
+            },
+        );
+    }
+
+    /// Enfileira um bloco para escrita. Os blocos devem ser enviados na ordem em que aparecem no arquivo.
+    pub fn write(&self, session: usize, data: Vec<u8>, pending: &AtomicUsize) {
+        pending.fetch_add(1, Ordering::SeqCst);
+        self.submit(session, DiskJob::Write { session, data });
+    }
+
+    /// Fecha o arquivo após a escrita de todos os blocos enfileirados; o fim é notificado com `DiskEvent::Finished`.
+    pub fn finish(&self, session: usize) {
+        self.submit(session, DiskJob::Finish { session });
+    }
+
+    /// Fecha o arquivo da sessão, sem notificação, quando a sessão é encerrada antes do fim da transferência, ou
+    /// quando termina o envio do arquivo pedido. O que já foi escrito permanece em disco.
+    pub fn close(&self, session: usize) {
+        self.submit(session, DiskJob::Close { session });
+    }
+
+    /// Como `close`, mas também remove o arquivo parcial.
+    pub fn discard(&self, session: usize) {
+        self.submit(session, DiskJob::Discard { session });
+    }
+
+    /// Abre o arquivo a ser enviado pela sessão; o tamanho é entregue com `DiskEvent::Opened`.
+    pub fn open(&self, session: usize, path: PathBuf) {
+        self.submit(session, DiskJob::Open { session, path });
+    }
+
+    /// Lê `count` blocos de `chunk_size` bytes do arquivo aberto com `open`, a partir de `offset`; eles são entregues
+    /// com `DiskEvent::ChunksRead`. No fim do arquivo, os blocos podem ser menores, ou vazios.
+    pub fn read(&self, session: usize, offset: u64, chunk_size: usize, count: usize) {
+        self.submit(
+            session,
+            DiskJob::Read {
+                session,
+                offset,
+                chunk_size,
+                count,
+            },
+        );
+    }
+
+    /// Retorna os resultados das operações de disco concluídas desde a última chamada.
+    pub fn take_events(&self) -> Vec<DiskEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    fn submit(&self, session: usize, job: DiskJob) {
+        let worker = &self.workers[session % self.workers.len()];
+        if worker.send(job).is_err() {
+            error!(session, "A thread de escrita da sessão foi finalizada");
+        }
+    }
+}
+
+/// Espera que as threads terminem as operações já enfileiradas, para que nenhum arquivo fique pela metade quando o
+/// servidor é encerrado.
+impl Drop for DiskPool {
+    fn drop(&mut self) {
+        self.workers.clear();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_worker(receiver: Receiver<DiskJob>, events: Arc<Mutex<Vec<DiskEvent>>>, waker: Arc<Waker>) {
+    let mut files: HashMap<usize, OpenFile> = HashMap::new();
+    // Arquivos abertos para leitura, pelas sessões que enviam um arquivo ao cliente.
+    let mut sources: HashMap<usize, File> = HashMap::new();
+
+    let notify = |event: DiskEvent| {
+        events.lock().unwrap().push(event);
+        if let Err(e) = waker.wake() {
+            error!("Falha ao acordar o laço de eventos: {}", e);
+        }
+    };
+
+    for job in receiver {
+        match job {
+            DiskJob::Create {
+                session,
+                path,
+                pending,
+            } => {
+                let file = create_output_directory(path.parent().unwrap_or_else(|| Path::new(".")))
+                    .and_then(|_| File::create(&path));
+                match file {
+                    Ok(file) => {
+                        files.insert(
+                            session,
+                            OpenFile {
+                                file,
+                                path,
+                                pending,
+                            },
+                        );
+                    }
+                    Err(error) => notify(DiskEvent::Failed { session, error }),
+                }
+            }
+            DiskJob::Write { session, data } => {
+                if let Some(open_file) = files.get_mut(&session) {
+                    let result = open_file.file.write_all(&data);
+                    open_file.pending.fetch_sub(1, Ordering::SeqCst);
+                    if let Err(error) = result {
+                        files.remove(&session);
+                        notify(DiskEvent::Failed { session, error });
+                    }
+                }
+            }
+            DiskJob::Finish { session } => {
+                if let Some(mut open_file) = files.remove(&session) {
+                    match open_file.file.flush() {
+                        Ok(()) => notify(DiskEvent::Finished { session }),
+                        Err(error) => notify(DiskEvent::Failed { session, error }),
+                    }
+                }
+            }
+            DiskJob::Close { session } => {
+                files.remove(&session);
+                sources.remove(&session);
+            }
+            DiskJob::Discard { session } => {
+                sources.remove(&session);
+                if let Some(OpenFile { file, path, .. }) = files.remove(&session) {
+                    drop(file);
+                    if let Err(e) = fs::remove_file(&path) {
+                        error!(
+                            session,
+                            "Falha ao remover o arquivo parcial {}: {}",
+                            path.display(),
+                            e
+                        );
+                    }
+                }
+            }
+            DiskJob::Open { session, path } => match open_source(&path) {
+                Ok((file, file_size)) => {
+                    sources.insert(session, file);
+                    notify(DiskEvent::Opened { session, file_size });
+                }
+                Err(error) => notify(DiskEvent::Failed { session, error }),
+            },
+            DiskJob::Read {
+                session,
+                offset,
+                chunk_size,
+                count,
+            } => {
+                if let Some(file) = sources.get_mut(&session) {
+                    match read_chunks(file, offset, chunk_size, count) {
+                        Ok(chunks) => notify(DiskEvent::ChunksRead { session, chunks }),
+                        Err(error) => {
+                            sources.remove(&session);
+                            notify(DiskEvent::Failed { session, error });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Abre um arquivo a ser enviado e retorna o seu tamanho. Diretórios e arquivos especiais não são servidos.
+fn open_source(path: &Path) -> Result<(File, u64), Error> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Err(Error::new(ErrorKind::NotFound, "Não é um arquivo"));
+    }
+    Ok((file, metadata.len()))
+}
+
+fn read_chunks(
+    file: &mut File,
+    offset: u64,
+    chunk_size: usize,
+    count: usize,
+) -> Result<Vec<Vec<u8>>, Error> {
+    file.seek(SeekFrom::Start(offset))?;
+    (0..count)
+        .map(|_| {
+            let mut chunk = Vec::with_capacity(chunk_size);
+            Read::by_ref(file)
+                .take(chunk_size as u64)
+                .read_to_end(&mut chunk)?;
+            Ok(chunk)
+        })
+        .collect()
+}
+
+fn create_output_directory(directory: &Path) -> Result<(), std::io::Error> {
+    match create_dir_all(directory) {
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::AlreadyExists => Ok(()),
+            kind => {
+                error!("Falha ao criar o diretório de saída: {:?}", kind);
+                Err(e)
+            }
+        },
+        Ok(()) => Ok(()),
+    }
+}
@@ -0,0 +1,20 @@
+//! Servidor do protocolo de transferência de arquivos: aceita conexões via TCP e recebe os blocos via UDP, salvando
+//! os arquivos no diretório de saída configurado em `ServerOptions`. Os arquivos desse diretório também podem ser
+//! baixados pelos clientes com a mensagem "Get".
+
+mod access;
+pub use access::IpNetwork;
+
+mod disk_pool;
+mod metrics;
+mod metrics_endpoint;
+mod reactor;
+mod session;
+mod udp_port_pool;
+
+mod server;
+pub use server::{
+    ReceivedFile, ReloadHandle, Server, ServerOptions, ShutdownHandle, ShutdownSummary,
+};
+
+pub use common::{GenericError, TransferInfo, TransferObserver, TransferStats};
@@ -1,273 +1,156 @@
-use core::panic;
-use std::fs::{create_dir, File};
-use std::net::TcpListener;
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
-use std::{env, usize};
-use std::{
-    io::Write,
-    net::{TcpStream, UdpSocket},
-};
 
-use std::sync::atomic::AtomicU16;
-use std::sync::Arc;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use tracing::{error, info, warn};
 
-use common::{send_message, ChunkData, FileData, GenericError, Message, MessageCreationError};
+use common::logging::{self, LogHandle};
+use servidor::{ReloadHandle, Server, ShutdownHandle};
 
 mod server_config;
 use server_config::ServerConfig;
 
 fn main() {
-    let config = ServerConfig::new(env::args()).unwrap_or_else(|err| {
-        eprintln!("Problema ao interpretar argumentos: {}", err);
-        process::exit(1);
+    let config = ServerConfig::load().unwrap_or_else(|e| {
+        eprintln!("Configuração inválida: {}", e);
+        process::exit(2);
     });
 
-    let address = format!("[::]:{}", config.port);
-    let udp_port = Arc::new(AtomicU16::new(30000));
-
-    println!("Fazendo bind em {}", address);
-    let listener = TcpListener::bind(address)
-        .expect(format!("Falha ao realizar bind na porta {}", config.port).as_str());
-
-    for stream in listener.incoming() {
-        // TODO: spawn new thread to handle connection
-        let stream = stream.unwrap();
-
-        let udp_port_clone = Arc::clone(&udp_port);
-        thread::spawn(move || {
-            if let Err(msg) = handle_connection(stream, udp_port_clone) {
-                match msg {
-                    GenericError::IO(e) => {
-                        eprintln!("{}", e);
-                    }
-                    GenericError::Logic(e) => {
-                        eprintln!("{}", e);
-                    }
-                }
+    let log_handle = logging::init(config.verbosity, &config.log_level, config.log_format);
+
+    let stats_json = Arc::new(AtomicBool::new(config.stats_json));
+    let print_json = Arc::clone(&stats_json);
+    let server = Server::bind_all(&config.listen, config.options.clone())
+        .unwrap_or_else(|e| {
+            eprintln!("Falha ao realizar bind em {:?}: {}", config.listen, e);
+            process::exit(1);
+        })
+        .on_file_received(move |file| {
+            if print_json.load(Ordering::Relaxed) {
+                println!("{}", file.stats.to_json());
+            } else {
+                print!(
+                    "{} recebido de {}\n{}",
+                    file.path.display(),
+                    file.peer,
+                    file.stats
+                );
             }
-            println!("Fechando conexão");
         });
 
-        println!("Uma thread foi criada para lidar com a conexão. Esperando novas conexões...");
+    let summary_json = Arc::clone(&stats_json);
+    let shutdown_handle = server.shutdown_handle();
+    match Signals::new([SIGTERM, SIGINT]) {
+        Ok(signals) => {
+            thread::spawn(move || shutdown_on_signal(signals, shutdown_handle));
+        }
+        Err(e) => warn!("Falha ao instalar o tratamento de SIGTERM e SIGINT: {}", e),
     }
-}
-
-fn handle_connection(mut stream: TcpStream, udp_port: Arc<AtomicU16>) -> Result<(), GenericError> {
-    // Wait for hello
-    common::receive_message(&mut stream)?;
-
-    let port = udp_port.fetch_add(10, std::sync::atomic::Ordering::SeqCst);
-    println!("Usará UDP na porta {}", port);
-    let udp_socket = UdpSocket::bind(("::", port)).expect("Não foi possível fazer bind UDP");
-
-    GenericError::transform_io(send_connection_message(port, &mut stream))?;
-
-    // Wait for info file
-    let message = common::receive_message(&mut stream)?;
-
-    let file_data = match message {
-        Message::InfoFile(file_data) => file_data,
-        _ => panic!("Tipo de mensagem inesperado"),
-    };
-
-    GenericError::transform_io(send_ok_message(&mut stream))?;
-    receive_file(&mut stream, udp_socket, file_data)
-}
-
-fn send_connection_message(udp_port: u16, stream: &mut TcpStream) -> Result<usize, std::io::Error> {
-    let data = build_connection_message(udp_port);
 
-    send_message(stream, &data)
-}
-
-fn send_ok_message(stream: &mut TcpStream) -> Result<usize, std::io::Error> {
-    let data = build_ok_message();
-
-    send_message(stream, &data)
-}
+    if config.path().is_some() {
+        let reload_handle = server.reload_handle();
+        match Signals::new([SIGHUP]) {
+            Ok(signals) => {
+                thread::spawn(move || {
+                    reload_on_sighup(signals, config, reload_handle, log_handle, stats_json)
+                });
+            }
+            Err(e) => warn!("Falha ao instalar o tratamento de SIGHUP: {}", e),
+        }
+    }
 
-fn build_ok_message() -> Vec<u8> {
-    vec![0, 4]
+    match server.run() {
+        Ok(summary) if summary_json.load(Ordering::Relaxed) => println!("{}", summary.to_json()),
+        Ok(summary) => print!("{}", summary),
+        Err(e) => {
+            eprintln!("Falha no laço de eventos: {}", e);
+            process::exit(1);
+        }
+    }
 }
 
-fn build_connection_message(udp_port: u16) -> Vec<u8> {
-    let mut connection: Vec<u8> = vec![0, 2];
-    connection.extend((udp_port as u32).to_be_bytes().iter());
-
-    connection
+/// O primeiro SIGTERM ou SIGINT encerra o servidor depois das transferências em andamento; os seguintes as
+/// interrompem imediatamente.
+fn shutdown_on_signal(mut signals: Signals, shutdown_handle: ShutdownHandle) {
+    for (received, signal) in signals.forever().enumerate() {
+        let result = if received == 0 {
+            info!(
+                signal,
+                "Sinal recebido, encerrando o servidor após as transferências em andamento"
+            );
+            shutdown_handle.shutdown()
+        } else {
+            warn!(
+                signal,
+                "Novo sinal recebido, interrompendo as transferências em andamento"
+            );
+            shutdown_handle.shutdown_now()
+        };
+        if let Err(e) = result {
+            error!("Falha ao pedir o encerramento do servidor: {}", e);
+        }
+    }
 }
 
-fn receive_file(
-    stream: &mut TcpStream,
-    udp_socket: UdpSocket,
-    file_data: FileData,
-) -> Result<(), GenericError> {
-    println!("Começando a receber o arquivo");
-    let expected_chunks = (file_data.file_size / 1000) + 1;
-    println!("Quantidade de blocos esperados={}", expected_chunks);
-    let mut contents: Vec<Vec<u8>> = vec![Vec::new(); expected_chunks as usize];
-    let mut acked_chunks = vec![false; expected_chunks as usize];
-    let mut received_chunks = vec![false; expected_chunks as usize];
-
-    let rws = 10;
-    let mut last_acceptable_chunk = rws;
-    let mut last_chunk_read = 0;
-
-    loop {
-        let mut buffer = [0; 1024];
-        let bytes_read = udp_socket.recv(&mut buffer);
-        let bytes_read = match bytes_read {
-            Ok(amt) => amt,
-            _ => 0,
+/// Relê o arquivo de configuração a cada SIGHUP e aplica os parâmetros que não dependem dos sockets já abertos. Uma
+/// configuração inválida é registrada e ignorada, mantendo a anterior. As alterações que exigem reinício são
+/// comparadas com `config`, a configuração com que o servidor foi iniciado.
+fn reload_on_sighup(
+    mut signals: Signals,
+    config: ServerConfig,
+    reload_handle: ReloadHandle,
+    log_handle: LogHandle,
+    stats_json: Arc<AtomicBool>,
+) {
+    for _signal in signals.forever() {
+        info!(path = ?config.path(), "SIGHUP recebido, relendo a configuração");
+        let new_config = match config.reload() {
+            Ok(new_config) => new_config,
+            Err(e) => {
+                error!("Configuração inválida, mantendo a anterior: {}", e);
+                continue;
+            }
         };
-        println!("{} bytes lidos do socket udp", bytes_read);
-        let message = GenericError::transform_logic(Message::new(&buffer, bytes_read));
-        match message {
-            Ok(Message::File(ChunkData {
-                sequence_number,
-                data,
-                ..
-            })) => {
-                println!("Bloco {} recebido", sequence_number);
-
-                let received_chunk_is_in_window = last_chunk_read <= sequence_number && sequence_number <= last_acceptable_chunk;
-                if received_chunk_is_in_window {
-                    received_chunks[sequence_number as usize] = true;
-                    println!(
-                        "Received chunk {}, which is inside the current window.",
-                        sequence_number
-                    );
-                    println!("LAF={}, LFR={}", last_acceptable_chunk, last_chunk_read);
-                    contents[sequence_number as usize] = data.clone();
-
-                    let all_received = received_chunks.iter().all(|item| *item);
-                    let should_send_ack = sequence_number == last_chunk_read
-                        || sequence_number == last_chunk_read + 1
-                        || all_received;
-                    if should_send_ack {
-                        let mut ack_sent = None;
-
-                        if all_received {
-                            println!("Todos os blocos recebidos. Enviando ack para o último.");
-
-                            let mut ack: Vec<u8> = vec![0, 7];
-                            let ack_idx: u32 = (expected_chunks - 1) as u32;
-
-                            ack.extend(ack_idx.to_be_bytes().iter());
-                            ack_sent = Some(ack_idx);
-
-                            GenericError::transform_io(send_message(stream, &ack))?;
-                        } else {
-                            for (idx, received) in received_chunks.iter().enumerate() {
-                                if !received && idx > 0 {
-                                    // Nesse caso, é enviado um ack cumulativo,
-                                    // considerando até o último bloco que já foi recebido de forma contígua
-                                    let mut ack: Vec<u8> = vec![0, 7];
-                                    let ack_idx: u32 = (idx as u32) - 1;
-
-                                    ack.extend(ack_idx.to_be_bytes().iter());
-                                    ack_sent = Some(ack_idx);
-
-                                    println!("Enviando ack para o bloco {}", ack_sent.unwrap());
-                                    GenericError::transform_io(send_message(stream, &ack))?;
-
-                                    for i in last_chunk_read..idx as u32 {
-                                        acked_chunks[i as usize] = true;
-                                    }
-                                    let amt = idx as u32 - last_chunk_read;
-                                    last_chunk_read += amt;
-                                    last_acceptable_chunk += amt;
-
-                                    println!(
-                                        "LAF={}, LFR={}",
-                                        last_acceptable_chunk, last_chunk_read
-                                    );
-                                    break;
-                                }
-                            }
-                        }
-
-                        let received_last = sequence_number as u64 == expected_chunks - 1;
-                        if ack_sent == None && received_last {
-                            println!("Enviando ack para o último bloco");
-                            let mut ack: Vec<u8> = vec![0, 7];
-                            let ack_idx: u32 = sequence_number;
-
-                            ack.extend(ack_idx.to_be_bytes().iter());
-                            GenericError::transform_io(send_message(stream, &ack))?;
-
-                            println!("Finalizando, uma vez que o último ack foi enviado");
-                            break;
-                        }
 
-                        if ack_sent.is_some() && ack_sent.unwrap() as u64 == expected_chunks - 1 {
-                            println!("Último ack enviado, finalizando");
-                            break;
-                        }
-                    }
-                } else {
-                    println!("Bloco recebido está fora da janela.");
-                    println!(
-                        "Enviando ack para o último bloco válido recebido, lfr={}, seqnum={}",
-                        last_chunk_read - 1,
-                        sequence_number
-                    );
-
-                    // Esse ack é enviado pois o cliente pode estar esperando um ack que foi perdido,
-                    // e está retransmitindo blocos que para o servidor já estão "acked"
-                    let mut ack: Vec<u8> = vec![0, 7];
-                    let ack_idx: u32 = last_chunk_read - 1;
-
-                    ack.extend(ack_idx.to_be_bytes().iter());
-
-                    GenericError::transform_io(send_message(stream, &ack))?;
-                    if ack_idx as u64 == expected_chunks - 1 {
-                        println!("Último ack enviado, finalizando");
-                        break;
-                    }
-                }
-            }
-            Ok(_msg) => {
-                let message = "Tipo de mensagem inválido";
-                println!("{}", message);
-                return Err(GenericError::Logic(MessageCreationError::new(
-                    message,
-                )));
+        let options = &new_config.options;
+        let requires_restart = [
+            ("server.listen", new_config.listen != config.listen),
+            (
+                "server.udp_port_range",
+                options.udp_port_range != config.options.udp_port_range,
+            ),
+            (
+                "server.shared_udp_port",
+                options.shared_udp_port != config.options.shared_udp_port,
+            ),
+            (
+                "limits.disk_workers",
+                options.disk_workers != config.options.disk_workers,
+            ),
+            (
+                "metrics.address",
+                options.metrics_address != config.options.metrics_address,
+            ),
+            ("logging.format", new_config.log_format != config.log_format),
+        ];
+        for (key, changed) in requires_restart {
+            if changed {
+                warn!(
+                    key,
+                    "Alteração ignorada: só tem efeito após reiniciar o servidor"
+                );
             }
-            Err(e) => return Err(e),
         }
-    }
-
-    GenericError::transform_io(create_output_directory())?;
-    let mut file =
-        GenericError::transform_io(File::create(format!("output/{}", file_data.filename)))?;
-
-    let contents = contents.iter().flat_map(|v| v.clone()).collect::<Vec<_>>();
-
-    GenericError::transform_io(file.write_all(&contents))?;
 
-    println!("Enviando mensagem de fim de transmissão.");
-    let fin: Vec<u8> = vec![0, 5];
-    let res = GenericError::transform_io(stream.write(&fin));
-
-    res.map(|_val| ())
-}
-
-fn create_output_directory() -> Result<(), std::io::Error>{
-    match create_dir("output") {
-        Err(e) => {
-            match e.kind() {
-                std::io::ErrorKind::AlreadyExists => {
-                    println!("Output folder already exists");
-                    Ok(())
-                },
-                kind => {
-                    println!("Unrecoverable error: {:?}", kind);
-                    return Err(e);
-                }
-            }
-        },
-        Ok(()) => Ok(())
+        if let Err(e) = log_handle.set_default_level(new_config.verbosity, &new_config.log_level) {
+            error!("Falha ao alterar o nível de log: {}", e);
+        }
+        stats_json.store(new_config.stats_json, Ordering::Relaxed);
+        if let Err(e) = reload_handle.reload(new_config.options) {
+            error!("Falha ao aplicar a nova configuração: {}", e);
+        }
     }
 }
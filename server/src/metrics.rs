@@ -0,0 +1,205 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Limites superiores, em segundos, das faixas do histograma de duração das transferências.
+const DURATION_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+/// Motivos pelos quais um datagrama recebido é descartado.
+#[derive(Clone, Copy)]
+pub enum DropReason {
+    /// O bloco está além da janela de recepção ou fora do arquivo.
+    OutOfWindow,
+    /// O datagrama não é uma mensagem "File" válida, ou o bloco não tem o tamanho anunciado.
+    Malformed,
+    /// O datagrama chegou quando a sessão não estava recebendo blocos.
+    Unexpected,
+    /// O datagrama não veio do cliente que abriu a sessão.
+    ForeignSource,
+    /// O datagrama não trouxe o token da sessão, quando ele é exigido.
+    InvalidToken,
+}
+
+impl DropReason {
+    const ALL: [DropReason; 5] = [
+        DropReason::OutOfWindow,
+        DropReason::Malformed,
+        DropReason::Unexpected,
+        DropReason::ForeignSource,
+        DropReason::InvalidToken,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            DropReason::OutOfWindow => "out_of_window",
+            DropReason::Malformed => "malformed",
+            DropReason::Unexpected => "unexpected",
+            DropReason::ForeignSource => "foreign_source",
+            DropReason::InvalidToken => "invalid_token",
+        }
+    }
+}
+
+/// Contadores do servidor, atualizados pelo laço de eventos e lidos pelo endpoint `/metrics`.
+#[derive(Default)]
+pub struct Metrics {
+    active_sessions: AtomicU64,
+    bytes_received: AtomicU64,
+    dropped_datagrams: [AtomicU64; DropReason::ALL.len()],
+    duplicate_chunks: AtomicU64,
+    udp_ports_in_use: AtomicU64,
+    completed_transfers: AtomicU64,
+    failed_transfers: AtomicU64,
+    expired_sessions: AtomicU64,
+    transfer_durations: Mutex<Histogram>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Quantidade de observações em cada faixa (não cumulativa); a última posição é a faixa `+Inf`.
+    counts: [u64; DURATION_BUCKETS.len() + 1],
+    sum: f64,
+}
+
+impl Metrics {
+    pub fn set_active_sessions(&self, sessions: usize) {
+        self.active_sessions.store(sessions as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_drop(&self, reason: DropReason) {
+        self.dropped_datagrams[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_duplicate(&self) {
+        self.duplicate_chunks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn udp_port_acquired(&self) {
+        self.udp_ports_in_use.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn udp_port_released(&self) {
+        self.udp_ports_in_use.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn record_completed(&self, duration: Duration) {
+        self.completed_transfers.fetch_add(1, Ordering::Relaxed);
+
+        let seconds = duration.as_secs_f64();
+        let bucket = DURATION_BUCKETS
+            .iter()
+            .position(|&limit| seconds <= limit)
+            .unwrap_or(DURATION_BUCKETS.len());
+        let mut histogram = self.transfer_durations.lock().unwrap();
+        histogram.counts[bucket] += 1;
+        histogram.sum += seconds;
+    }
+
+    pub fn record_failed(&self) {
+        self.failed_transfers.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Conta uma sessão encerrada por um dos prazos; ela também é contada como falha por `record_failed`.
+    pub fn record_expired(&self) {
+        self.expired_sessions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn completed_transfers(&self) -> u64 {
+        self.completed_transfers.load(Ordering::Relaxed)
+    }
+
+    pub fn failed_transfers(&self) -> u64 {
+        self.failed_transfers.load(Ordering::Relaxed)
+    }
+
+    /// Representação no formato de texto do Prometheus (versão 0.0.4).
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        gauge(
+            &mut output,
+            "udp_transfer_active_sessions",
+            "Sessões abertas no momento.",
+            self.active_sessions.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut output,
+            "udp_transfer_bytes_received_total",
+            "Bytes de conteúdo recebidos em datagramas de dados, incluindo duplicados.",
+            self.bytes_received.load(Ordering::Relaxed),
+        );
+
+        let name = "udp_transfer_datagrams_dropped_total";
+        header(&mut output, name, "Datagramas descartados, por motivo.", "counter");
+        for reason in DropReason::ALL {
+            let value = self.dropped_datagrams[reason as usize].load(Ordering::Relaxed);
+            let _ = writeln!(output, "{}{{reason=\"{}\"}} {}", name, reason.label(), value);
+        }
+
+        counter(
+            &mut output,
+            "udp_transfer_duplicate_chunks_total",
+            "Blocos recebidos mais de uma vez, por retransmissão do cliente.",
+            self.duplicate_chunks.load(Ordering::Relaxed),
+        );
+        gauge(
+            &mut output,
+            "udp_transfer_udp_ports_in_use",
+            "Portas UDP alocadas a sessões.",
+            self.udp_ports_in_use.load(Ordering::Relaxed),
+        );
+
+        let name = "udp_transfer_transfers_total";
+        header(&mut output, name, "Transferências encerradas, por resultado.", "counter");
+        let completed = self.completed_transfers.load(Ordering::Relaxed);
+        let failed = self.failed_transfers.load(Ordering::Relaxed);
+        let _ = writeln!(output, "{}{{result=\"completed\"}} {}", name, completed);
+        let _ = writeln!(output, "{}{{result=\"failed\"}} {}", name, failed);
+        counter(
+            &mut output,
+            "udp_transfer_sessions_expired_total",
+            "Sessões encerradas por exceder o prazo de handshake, de inatividade ou de transferência.",
+            self.expired_sessions.load(Ordering::Relaxed),
+        );
+
+        let name = "udp_transfer_duration_seconds";
+        header(
+            &mut output,
+            name,
+            "Duração das transferências concluídas, do anúncio do arquivo ao fim da escrita.",
+            "histogram",
+        );
+        let histogram = self.transfer_durations.lock().unwrap();
+        let mut cumulative = 0;
+        for (limit, count) in DURATION_BUCKETS.iter().zip(histogram.counts.iter()) {
+            cumulative += count;
+            let _ = writeln!(output, "{}_bucket{{le=\"{}\"}} {}", name, limit, cumulative);
+        }
+        cumulative += histogram.counts[DURATION_BUCKETS.len()];
+        let _ = writeln!(output, "{}_bucket{{le=\"+Inf\"}} {}", name, cumulative);
+        let _ = writeln!(output, "{}_sum {}", name, histogram.sum);
+        let _ = writeln!(output, "{}_count {}", name, cumulative);
+
+        output
+    }
+}
+
+fn header(output: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, kind);
+}
+
+fn counter(output: &mut String, name: &str, help: &str, value: u64) {
+    header(output, name, help, "counter");
+    let _ = writeln!(output, "{} {}", name, value);
+}
+
+fn gauge(output: &mut String, name: &str, help: &str, value: u64) {
+    header(output, name, help, "gauge");
+    let _ = writeln!(output, "{} {}", name, value);
+}
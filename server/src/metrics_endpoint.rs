@@ -0,0 +1,67 @@
+use std::io::{BufRead, BufReader, Error, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::metrics::Metrics;
+
+/// Tempo máximo de espera pela requisição de um cliente, para que uma conexão parada não bloqueie o endpoint.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Inicia, numa thread própria, o servidor HTTP que responde `GET /metrics` com as métricas no formato do
+/// Prometheus. Retorna o endereço em que ele aceita conexões.
+pub fn spawn(address: SocketAddr, metrics: Arc<Metrics>) -> Result<SocketAddr, Error> {
+    let listener = TcpListener::bind(address)?;
+    let local_address = listener.local_addr()?;
+    info!(address = %local_address, "Endpoint de métricas disponível em /metrics");
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| handle_connection(stream, &metrics));
+            if let Err(e) = result {
+                warn!("Falha ao responder requisição de métricas: {}", e);
+            }
+        }
+    });
+
+    Ok(local_address)
+}
+
+fn handle_connection(stream: TcpStream, metrics: &Metrics) -> Result<(), Error> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Os cabeçalhos não são usados, mas são lidos para que o cliente não receba um reset ao fechar a conexão.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && header.trim_end() != "" {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = metrics.render();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        }
+        (Some("GET"), Some(_path)) => {
+            String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+        }
+        _ => String::from(
+            "HTTP/1.1 405 Method Not Allowed\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ),
+    };
+
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
@@ -0,0 +1,726 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::net::{Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mio::net::{TcpListener, UdpSocket};
+use mio::{Events, Interest, Poll, Token, Waker};
+use tracing::{error, info, warn};
+
+use common::{ErrorCode, GenericError, Message, NoopObserver, TransferObserver, MAX_DATAGRAM_SIZE};
+
+use crate::disk_pool::{DiskEvent, DiskPool};
+use crate::metrics::{DropReason, Metrics};
+use crate::session::Session;
+use crate::udp_port_pool::{UdpPortPool, UdpPorts};
+use crate::{ReceivedFile, ServerOptions, ShutdownSummary};
+
+/// Token usado pelas threads de disco, pela releitura da configuração e pelo pedido de encerramento para acordar o
+/// laço de eventos.
+const WAKER: Token = Token(usize::MAX);
+/// Token do socket UDP compartilhado pelas sessões, quando `shared_udp_port` está definida.
+const SHARED_DATA: Token = Token(usize::MAX - 1);
+/// Token do primeiro socket TCP que aceita novas conexões; os demais usam os tokens imediatamente abaixo.
+const FIRST_LISTENER: usize = usize::MAX - 2;
+
+type FileReceivedCallback = Box<dyn FnMut(&ReceivedFile)>;
+type SessionErrorCallback = Box<dyn FnMut(SocketAddr, &GenericError)>;
+type ObserverFactory = Box<dyn FnMut() -> Box<dyn TransferObserver>>;
+
+/// Funções chamadas pelo laço de eventos quando uma sessão começa ou termina.
+#[derive(Default)]
+pub struct Callbacks {
+    pub on_file_received: Option<FileReceivedCallback>,
+    pub on_session_error: Option<SessionErrorCallback>,
+    /// Cria o observador de cada nova sessão.
+    pub observer_factory: Option<ObserverFactory>,
+}
+
+/// Novos parâmetros entregues ao laço de eventos por outra thread, aplicados quando ele é acordado.
+#[derive(Clone)]
+pub struct PendingReload {
+    options: Arc<Mutex<Option<ServerOptions>>>,
+    waker: Arc<Waker>,
+}
+
+impl PendingReload {
+    pub fn submit(&self, options: ServerOptions) -> Result<(), Error> {
+        *self.options.lock().unwrap() = Some(options);
+        self.waker.wake()
+    }
+}
+
+/// Forma de encerramento pedida ao laço de eventos; um pedido `Immediate` prevalece sobre um `Graceful`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShutdownMode {
+    /// Espera as transferências em andamento por até `shutdown_grace`.
+    Graceful,
+    /// Interrompe imediatamente todas as sessões.
+    Immediate,
+}
+
+/// Pedido de encerramento entregue ao laço de eventos por outra thread.
+#[derive(Clone)]
+pub struct PendingShutdown {
+    mode: Arc<Mutex<Option<ShutdownMode>>>,
+    waker: Arc<Waker>,
+}
+
+impl PendingShutdown {
+    pub fn submit(&self, mode: ShutdownMode) -> Result<(), Error> {
+        {
+            let mut pending = self.mode.lock().unwrap();
+            *pending = (*pending).max(Some(mode));
+        }
+        self.waker.wake()
+    }
+}
+
+/// Andamento do encerramento do servidor.
+struct Shutdown {
+    requested_at: Instant,
+    /// Momento em que as sessões restantes são interrompidas.
+    deadline: Instant,
+    /// Sessões que concluíram a transferência depois do pedido de encerramento.
+    drained: usize,
+    /// Sessões encerradas com o erro `ShuttingDown`.
+    interrupted: usize,
+}
+
+/// Laço de eventos do servidor: uma única thread multiplexa os sockets de escuta e os sockets TCP e UDP de todas as
+/// sessões, enquanto a escrita em disco é feita pelo `DiskPool`.
+pub struct Reactor {
+    poll: Poll,
+    listeners: Vec<TcpListener>,
+    /// Se os sockets de escuta estão registrados; deixam de estar quando o limite de sessões é atingido.
+    listening: bool,
+    sessions: HashMap<usize, Session>,
+    /// Prazos das sessões, o mais próximo no topo: o do pacer ou da retransmissão, e o de expiração. Quando o prazo de
+    /// uma sessão muda, a entrada antiga fica obsoleta e é descartada ao chegar ao topo.
+    timers: BinaryHeap<Reverse<(Instant, usize)>>,
+    /// Prazo de cada sessão que ainda tem uma entrada válida em `timers`.
+    scheduled: HashMap<usize, Instant>,
+    /// Sessões que aguardam o socket UDP compartilhado voltar a aceitar datagramas.
+    blocked_senders: HashSet<usize>,
+    disk_pool: DiskPool,
+    next_session_id: usize,
+    udp_ports: UdpPorts,
+    /// Parâmetros do servidor, compartilhados com as sessões. Uma releitura troca o valor usado pelas novas sessões,
+    /// enquanto as sessões em andamento continuam com os parâmetros com que começaram.
+    options: Arc<ServerOptions>,
+    pending_reload: PendingReload,
+    pending_shutdown: PendingShutdown,
+    /// Definido a partir do pedido de encerramento: não são aceitas novas conexões e `run` retorna quando não restam
+    /// sessões.
+    shutdown: Option<Shutdown>,
+    metrics: Arc<Metrics>,
+    pub callbacks: Callbacks,
+}
+
+impl Reactor {
+    pub fn new(
+        addresses: &[SocketAddr],
+        options: ServerOptions,
+        metrics: Arc<Metrics>,
+    ) -> Result<Reactor, Error> {
+        let poll = Poll::new()?;
+        let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+
+        let mut listeners = Vec::with_capacity(addresses.len());
+        for (index, &address) in addresses.iter().enumerate() {
+            info!(%address, "Fazendo bind");
+            let mut listener = TcpListener::bind(address)?;
+            poll.registry()
+                .register(&mut listener, listener_token(index), Interest::READABLE)?;
+            listeners.push(listener);
+        }
+
+        let udp_ports = match options.shared_udp_port {
+            Some(port) => {
+                let address = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port);
+                let mut socket = UdpSocket::bind(address)?;
+                let port = socket.local_addr()?.port();
+                info!(
+                    udp_port = port,
+                    "Usando uma porta UDP compartilhada entre as sessões"
+                );
+                poll.registry().register(
+                    &mut socket,
+                    SHARED_DATA,
+                    Interest::READABLE | Interest::WRITABLE,
+                )?;
+                metrics.udp_port_acquired();
+                UdpPorts::Shared { socket, port }
+            }
+            None => UdpPorts::Pool(UdpPortPool::new(
+                options.udp_port_range.clone(),
+                Arc::clone(&metrics),
+            )),
+        };
+
+        Ok(Reactor {
+            poll,
+            listeners,
+            listening: true,
+            sessions: HashMap::new(),
+            timers: BinaryHeap::new(),
+            scheduled: HashMap::new(),
+            blocked_senders: HashSet::new(),
+            disk_pool: DiskPool::new(options.disk_workers, Arc::clone(&waker)),
+            next_session_id: 0,
+            udp_ports,
+            options: Arc::new(options),
+            pending_reload: PendingReload {
+                options: Arc::new(Mutex::new(None)),
+                waker: Arc::clone(&waker),
+            },
+            pending_shutdown: PendingShutdown {
+                mode: Arc::new(Mutex::new(None)),
+                waker,
+            },
+            shutdown: None,
+            metrics,
+            callbacks: Callbacks::default(),
+        })
+    }
+
+    pub fn local_addrs(&self) -> Result<Vec<SocketAddr>, Error> {
+        self.listeners
+            .iter()
+            .map(|listener| listener.local_addr())
+            .collect()
+    }
+
+    pub fn pending_reload(&self) -> PendingReload {
+        self.pending_reload.clone()
+    }
+
+    pub fn pending_shutdown(&self) -> PendingShutdown {
+        self.pending_shutdown.clone()
+    }
+
+    /// Executa o laço de eventos até que, depois de um pedido de encerramento, não reste nenhuma sessão.
+    pub fn run(&mut self) -> Result<ShutdownSummary, Error> {
+        let mut events = Events::with_capacity(1024);
+
+        loop {
+            let mut timeout = self.run_session_timers();
+            if self.shutdown.is_some() {
+                match self.drain_sessions() {
+                    Some(remaining) => timeout = earliest(timeout, Some(remaining)),
+                    None => return Ok(self.shutdown_summary()),
+                }
+            }
+            if let Err(e) = self.poll.poll(&mut events, timeout) {
+                if e.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+
+            for event in events.iter() {
+                match event.token() {
+                    WAKER => {
+                        self.handle_disk_events();
+                        self.apply_pending_reload();
+                        self.apply_pending_shutdown();
+                    }
+                    SHARED_DATA => {
+                        if event.is_writable() {
+                            self.resume_shared_sending();
+                        }
+                        if event.is_readable() {
+                            self.dispatch_shared_datagrams();
+                        }
+                    }
+                    Token(token) if FIRST_LISTENER - token < self.listeners.len() => {
+                        self.accept_connections(FIRST_LISTENER - token)
+                    }
+                    Token(token) => {
+                        self.handle_session_event(token / 2, token % 2 == 1, event.is_writable())
+                    }
+                }
+            }
+        }
+    }
+
+    fn accept_connections(&mut self, listener: usize) {
+        while self.sessions.len() < self.options.max_sessions {
+            let (stream, peer) = match self.listeners[listener].accept() {
+                Ok(connection) => connection,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    warn!("Falha ao aceitar conexão: {}", e);
+                    return;
+                }
+            };
+
+            let allowed_clients = &self.options.allowed_clients;
+            if !allowed_clients.is_empty()
+                && !allowed_clients
+                    .iter()
+                    .any(|network| network.contains(peer.ip()))
+            {
+                warn!(%peer, "Conexão recusada: endereço não autorizado");
+                continue;
+            }
+
+            let id = self.next_session_id;
+            self.next_session_id += 1;
+
+            let observer = match &mut self.callbacks.observer_factory {
+                Some(observer_factory) => observer_factory(),
+                None => Box::new(NoopObserver),
+            };
+            let mut session = Session::new(
+                id,
+                peer,
+                stream,
+                observer,
+                Arc::clone(&self.options),
+                Arc::clone(&self.metrics),
+            );
+            let span = session.span().clone();
+            let _entered = span.enter();
+            if let Err(e) = session.register(self.poll.registry()) {
+                warn!("Falha ao registrar conexão: {}", e);
+                continue;
+            }
+            self.sessions.insert(id, session);
+            self.schedule(id);
+            self.metrics.set_active_sessions(self.sessions.len());
+            info!(active_sessions = self.sessions.len(), "Conexão aceita");
+        }
+
+        // Limite de sessões atingido: as novas conexões aguardam na fila do sistema operacional até que uma sessão
+        // termine.
+        self.update_listening();
+    }
+
+    /// Para de aceitar conexões enquanto o limite de sessões estiver atingido, e volta a aceitá-las quando houver
+    /// espaço.
+    fn update_listening(&mut self) {
+        if self.shutdown.is_some() {
+            return;
+        }
+
+        let has_room = self.sessions.len() < self.options.max_sessions;
+        if self.listening == has_room {
+            return;
+        }
+
+        if has_room {
+            for (index, listener) in self.listeners.iter_mut().enumerate() {
+                if let Err(e) = self.poll.registry().register(
+                    listener,
+                    listener_token(index),
+                    Interest::READABLE,
+                ) {
+                    error!("{}", e);
+                }
+            }
+            self.listening = true;
+            // Conexões que chegaram durante a pausa não geram um novo evento de leitura.
+            for index in 0..self.listeners.len() {
+                self.accept_connections(index);
+            }
+        } else {
+            warn!(
+                max_sessions = self.options.max_sessions,
+                "Limite de sessões simultâneas atingido, pausando novas conexões"
+            );
+            for listener in &mut self.listeners {
+                if let Err(e) = self.poll.registry().deregister(listener) {
+                    error!("{}", e);
+                }
+            }
+            self.listening = false;
+        }
+    }
+
+    /// Passa a usar os parâmetros entregues por `PendingReload`, com exceção dos que dependem de recursos já criados:
+    /// a quantidade de threads de disco, o endpoint de métricas e a faixa de portas UDP.
+    fn apply_pending_reload(&mut self) {
+        let mut options = match self.pending_reload.options.lock().unwrap().take() {
+            Some(options) => options,
+            None => return,
+        };
+
+        options.disk_workers = self.options.disk_workers;
+        options.metrics_address = self.options.metrics_address;
+        options.udp_port_range = self.options.udp_port_range.clone();
+        options.shared_udp_port = self.options.shared_udp_port;
+        info!(
+            max_sessions = options.max_sessions,
+            receive_window = options.receive_window,
+            max_chunk_size = options.max_chunk_size,
+            max_file_size = options.max_file_size,
+            output_dir = %options.output_dir.display(),
+            allowed_clients = options.allowed_clients.len(),
+            require_token = options.require_token,
+            "Configuração recarregada"
+        );
+        self.options = Arc::new(options);
+        self.update_listening();
+    }
+
+    /// Inicia o encerramento pedido por `PendingShutdown`, ou antecipa o prazo de um encerramento já iniciado. Os
+    /// sockets de escuta são fechados, as sessões que ainda não começaram a transferência são interrompidas, e as que
+    /// aguardam o próximo arquivo são encerradas.
+    fn apply_pending_shutdown(&mut self) {
+        let mode = match self.pending_shutdown.mode.lock().unwrap().take() {
+            Some(mode) => mode,
+            None => return,
+        };
+        let now = Instant::now();
+        let deadline = match mode {
+            ShutdownMode::Graceful => now + self.options.shutdown_grace,
+            ShutdownMode::Immediate => now,
+        };
+
+        if let Some(shutdown) = &mut self.shutdown {
+            shutdown.deadline = shutdown.deadline.min(deadline);
+            return;
+        }
+
+        info!(
+            active_sessions = self.sessions.len(),
+            grace = ?deadline.duration_since(now),
+            "Encerrando o servidor: novas conexões não são mais aceitas"
+        );
+        if self.listening {
+            for listener in &mut self.listeners {
+                if let Err(e) = self.poll.registry().deregister(listener) {
+                    error!("{}", e);
+                }
+            }
+            self.listening = false;
+        }
+        self.listeners.clear();
+        self.shutdown = Some(Shutdown {
+            requested_at: now,
+            deadline,
+            drained: 0,
+            interrupted: 0,
+        });
+
+        let not_started: Vec<(usize, bool)> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| !session.has_started_transfer())
+            .map(|(&id, session)| (id, session.is_idle()))
+            .collect();
+        for (id, idle) in not_started {
+            if idle {
+                self.after_session_event(id, Ok(()));
+            } else {
+                self.interrupt_session(id);
+            }
+        }
+    }
+
+    /// Interrompe as sessões restantes quando o prazo do encerramento expira. Retorna quanto falta para o prazo, ou
+    /// `None` se não restam sessões.
+    fn drain_sessions(&mut self) -> Option<Duration> {
+        let deadline = self.shutdown.as_ref()?.deadline;
+        let now = Instant::now();
+        if deadline <= now {
+            let remaining: Vec<usize> = self.sessions.keys().copied().collect();
+            for id in remaining {
+                self.interrupt_session(id);
+            }
+        }
+
+        if self.sessions.is_empty() {
+            None
+        } else {
+            Some(deadline.saturating_duration_since(now))
+        }
+    }
+
+    /// Encerra a sessão por causa do encerramento do servidor, avisando o cliente e removendo o arquivo parcial.
+    fn interrupt_session(&mut self, id: usize) {
+        let session = match self.sessions.get_mut(&id) {
+            Some(session) => session,
+            None => return,
+        };
+        let span = session.span().clone();
+        let _entered = span.enter();
+        session.discard_partial_file();
+        if let Some(shutdown) = &mut self.shutdown {
+            shutdown.interrupted += 1;
+        }
+        self.after_session_event(id, Err(GenericError::Protocol(ErrorCode::ShuttingDown)));
+    }
+
+    fn shutdown_summary(&self) -> ShutdownSummary {
+        let (drained_sessions, interrupted_sessions, drain_time) = match &self.shutdown {
+            Some(shutdown) => (
+                shutdown.drained,
+                shutdown.interrupted,
+                shutdown.requested_at.elapsed(),
+            ),
+            None => (0, 0, Duration::ZERO),
+        };
+        ShutdownSummary {
+            completed_transfers: self.metrics.completed_transfers(),
+            failed_transfers: self.metrics.failed_transfers(),
+            drained_sessions,
+            interrupted_sessions,
+            drain_time,
+        }
+    }
+
+    fn handle_session_event(&mut self, id: usize, is_data: bool, writable: bool) {
+        let session = match self.sessions.get_mut(&id) {
+            Some(session) => session,
+            None => return,
+        };
+        let span = session.span().clone();
+        let _entered = span.enter();
+
+        let registry = self.poll.registry();
+        let result = if is_data {
+            let resumed = if writable {
+                session.on_data_writable(&self.udp_ports)
+            } else {
+                Ok(())
+            };
+            match resumed {
+                Ok(()) => session.on_data_readable(&self.disk_pool),
+                Err(e) => Err(e),
+            }
+        } else {
+            session.on_control_event(registry, &self.disk_pool, &mut self.udp_ports)
+        };
+
+        self.after_session_event(id, result);
+    }
+
+    /// Atende as sessões cujo prazo venceu e retorna quanto falta para o próximo prazo.
+    fn run_session_timers(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        while let Some(&Reverse((timer, id))) = self.timers.peek() {
+            if timer > now {
+                break;
+            }
+            self.timers.pop();
+            if self.scheduled.get(&id) == Some(&timer) {
+                self.scheduled.remove(&id);
+                due.push(id);
+            }
+        }
+
+        for id in due {
+            self.run_session_timer(id, now);
+        }
+
+        self.timers
+            .peek()
+            .map(|Reverse((timer, _id))| timer.saturating_duration_since(Instant::now()))
+    }
+
+    /// Encerra a sessão se ela expirou, avisando o cliente, ou retoma o envio se o prazo do pacer ou da retransmissão
+    /// venceu. Se o prazo mudou desde que foi agendado, apenas agenda o novo.
+    fn run_session_timer(&mut self, id: usize, now: Instant) {
+        let session = match self.sessions.get_mut(&id) {
+            Some(session) => session,
+            None => return,
+        };
+        let span = session.span().clone();
+        let _entered = span.enter();
+
+        match session.deadline() {
+            Some((deadline, code)) if deadline <= now => {
+                self.metrics.record_expired();
+                self.after_session_event(id, Err(GenericError::Protocol(code)));
+            }
+            _ if session.next_timer().is_some_and(|timer| timer <= now) => {
+                let result = session.on_timer(&self.udp_ports);
+                self.after_session_event(id, result);
+            }
+            _ => self.schedule(id),
+        }
+    }
+
+    /// Agenda o próximo prazo da sessão, caso seja anterior ao já agendado, e a coloca na fila das que aguardam o
+    /// socket UDP compartilhado, caso um envio tenha sido bloqueado.
+    fn schedule(&mut self, id: usize) {
+        let session = match self.sessions.get(&id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        if session.is_blocked_on_send() && matches!(self.udp_ports, UdpPorts::Shared { .. }) {
+            self.blocked_senders.insert(id);
+        }
+
+        let next = match (session.next_timer(), session.deadline()) {
+            (Some(timer), Some((deadline, _code))) => Some(timer.min(deadline)),
+            (timer, deadline) => timer.or(deadline.map(|(deadline, _code)| deadline)),
+        };
+        if let Some(next) = next {
+            if self
+                .scheduled
+                .get(&id)
+                .is_none_or(|&scheduled| next < scheduled)
+            {
+                self.scheduled.insert(id, next);
+                self.timers.push(Reverse((next, id)));
+            }
+        }
+    }
+
+    /// Avisa as sessões bloqueadas no envio que o socket UDP compartilhado voltou a aceitar datagramas.
+    fn resume_shared_sending(&mut self) {
+        for id in std::mem::take(&mut self.blocked_senders) {
+            if let Some(session) = self.sessions.get_mut(&id) {
+                let span = session.span().clone();
+                let _entered = span.enter();
+                let result = session.on_data_writable(&self.udp_ports);
+                self.after_session_event(id, result);
+            }
+        }
+    }
+
+    /// Lê os datagramas do socket compartilhado e entrega cada bloco à sessão indicada nele. Datagramas inválidos ou
+    /// de sessões inexistentes são descartados sem afetar as demais sessões.
+    fn dispatch_shared_datagrams(&mut self) {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            let (bytes_read, source) = match &self.udp_ports {
+                UdpPorts::Shared { socket, .. } => match socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        warn!("Falha ao ler do socket UDP compartilhado: {}", e);
+                        return;
+                    }
+                },
+                UdpPorts::Pool(_) => return,
+            };
+
+            let chunk = match Message::new(&buffer, bytes_read) {
+                Ok(Message::File(chunk)) => chunk,
+                _ => {
+                    self.metrics.record_drop(DropReason::Malformed);
+                    continue;
+                }
+            };
+            let id = chunk.session_id as usize;
+            let session = match self.sessions.get_mut(&id) {
+                Some(session) => session,
+                None => {
+                    self.metrics.record_drop(DropReason::Unexpected);
+                    continue;
+                }
+            };
+            let span = session.span().clone();
+            let _entered = span.enter();
+
+            let result = session.on_chunk(chunk, source, &self.disk_pool);
+            self.after_session_event(id, result);
+        }
+    }
+
+    fn handle_disk_events(&mut self) {
+        for event in self.disk_pool.take_events() {
+            let id = match &event {
+                DiskEvent::Finished { session }
+                | DiskEvent::Opened { session, .. }
+                | DiskEvent::ChunksRead { session, .. }
+                | DiskEvent::Failed { session, .. } => *session,
+            };
+
+            if let Some(session) = self.sessions.get_mut(&id) {
+                let span = session.span().clone();
+                let _entered = span.enter();
+                let result = session.on_disk_event(event, &self.disk_pool, &self.udp_ports);
+                self.after_session_event(id, result);
+            }
+        }
+    }
+
+    /// Encerra a sessão caso ela tenha terminado ou falhado.
+    fn after_session_event(&mut self, id: usize, result: Result<(), GenericError>) {
+        if let Err(e) = &result {
+            warn!("Sessão encerrada por erro: {}", e);
+        }
+
+        let session = match self.sessions.get_mut(&id) {
+            Some(session) => session,
+            None => return,
+        };
+
+        if let Some(transfer) = session.take_completed_transfer() {
+            self.metrics.record_completed(transfer.stats.elapsed);
+            if let (Some(on_file_received), Some(file)) = (
+                &mut self.callbacks.on_file_received,
+                &transfer.received_file,
+            ) {
+                on_file_received(file);
+            }
+        }
+
+        match &result {
+            Err(e) => {
+                // Entre dois arquivos não há transferência em andamento para ser contada como falha.
+                if !session.is_idle() {
+                    self.metrics.record_failed();
+                }
+                session.on_error(e);
+                if let Some(on_session_error) = &mut self.callbacks.on_session_error {
+                    on_session_error(session.peer(), e);
+                }
+            }
+            Ok(()) if session.is_finished() => {
+                if let Some(shutdown) = &mut self.shutdown {
+                    shutdown.drained += 1;
+                }
+            }
+            // Durante o encerramento, a sessão não recebe outro arquivo depois de concluir o atual.
+            Ok(()) if self.shutdown.is_some() && session.is_idle() => {
+                session.notify(ErrorCode::ShuttingDown);
+                if let Some(shutdown) = &mut self.shutdown {
+                    shutdown.drained += 1;
+                }
+            }
+            Ok(()) => {
+                self.schedule(id);
+                return;
+            }
+        }
+
+        self.close_session(id);
+    }
+
+    fn close_session(&mut self, id: usize) {
+        if let Some(session) = self.sessions.remove(&id) {
+            let span = session.span().clone();
+            let _entered = span.enter();
+            session.close(self.poll.registry(), &self.disk_pool, &mut self.udp_ports);
+            self.scheduled.remove(&id);
+            self.blocked_senders.remove(&id);
+            self.metrics.set_active_sessions(self.sessions.len());
+            info!("Fechando conexão");
+        }
+
+        self.update_listening();
+    }
+}
+
+/// O menor entre dois prazos opcionais.
+fn earliest(first: Option<Duration>, second: Option<Duration>) -> Option<Duration> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.min(second)),
+        (first, second) => first.or(second),
+    }
+}
+
+fn listener_token(index: usize) -> Token {
+    Token(FIRST_LISTENER - index)
+}
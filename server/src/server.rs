@@ -0,0 +1,295 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{GenericError, TransferObserver, TransferStats, MAX_CHUNK_SIZE};
+
+use crate::access::IpNetwork;
+use crate::metrics::Metrics;
+use crate::metrics_endpoint;
+use crate::reactor::{PendingReload, PendingShutdown, Reactor, ShutdownMode};
+
+/// Maior arquivo aceito por padrão: 10 GiB.
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 << 30;
+
+/// Parâmetros do servidor.
+#[derive(Clone, Debug)]
+pub struct ServerOptions {
+    /// Quantidade máxima de sessões simultâneas; conexões além dela aguardam na fila do sistema operacional.
+    pub max_sessions: usize,
+    /// Quantidade de threads que fazem a escrita dos arquivos em disco.
+    pub disk_workers: usize,
+    /// Endereço do endpoint HTTP `/metrics`, no formato do Prometheus. Sem ele, as métricas não são expostas.
+    pub metrics_address: Option<SocketAddr>,
+    /// Quantidade máxima de blocos que o servidor aceita além do último bloco confirmado.
+    pub receive_window: u16,
+    /// Maior tamanho de bloco aceito; transferências anunciadas com blocos maiores são recusadas.
+    pub max_chunk_size: usize,
+    /// Maior arquivo aceito, em bytes; anúncios de arquivos maiores são recusados antes de qualquer alocação, com o
+    /// erro `FileTooLarge`.
+    pub max_file_size: u64,
+    /// Diretório onde os arquivos recebidos são salvos, e de onde são servidos os downloads.
+    pub output_dir: PathBuf,
+    /// Portas em que são abertos os sockets UDP das sessões.
+    pub udp_port_range: RangeInclusive<u16>,
+    /// Porta UDP compartilhada por todas as sessões. Quando definida, substitui `udp_port_range`: os blocos de todos
+    /// os clientes chegam num único socket e são distribuídos pelo identificador de sessão.
+    pub shared_udp_port: Option<u16>,
+    /// Faixas de endereços dos clientes aceitos; vazia, aceita qualquer cliente.
+    pub allowed_clients: Vec<IpNetwork>,
+    /// Tempo máximo entre a conexão e o anúncio do arquivo.
+    pub handshake_timeout: Duration,
+    /// Tempo máximo sem mensagens nem blocos do cliente durante a transferência.
+    pub idle_timeout: Duration,
+    /// Duração máxima de uma transferência, a partir do anúncio do arquivo; sem limite quando `None`.
+    pub transfer_timeout: Option<Duration>,
+    /// Tempo que as transferências em andamento têm para terminar depois de pedido o encerramento do servidor.
+    pub shutdown_grace: Duration,
+    /// Exige que os blocos tragam o token aleatório enviado a cada cliente na mensagem "Connection". Mesmo sem ele,
+    /// só são aceitos blocos vindos do endereço IP da conexão TCP.
+    pub require_token: bool,
+}
+
+impl Default for ServerOptions {
+    fn default() -> ServerOptions {
+        ServerOptions {
+            max_sessions: 1024,
+            disk_workers: 4,
+            metrics_address: None,
+            receive_window: 10,
+            max_chunk_size: MAX_CHUNK_SIZE,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            output_dir: PathBuf::from("output"),
+            udp_port_range: 30000..=39999,
+            shared_udp_port: None,
+            allowed_clients: Vec::new(),
+            handshake_timeout: Duration::from_secs(10),
+            idle_timeout: Duration::from_secs(30),
+            transfer_timeout: None,
+            shutdown_grace: Duration::from_secs(30),
+            require_token: false,
+        }
+    }
+}
+
+/// Um arquivo recebido por completo e escrito em disco.
+pub struct ReceivedFile {
+    /// Endereço do cliente que enviou o arquivo.
+    pub peer: SocketAddr,
+    pub path: PathBuf,
+    pub file_size: u64,
+    /// Estatísticas do recebimento, do anúncio do arquivo até o fim da escrita em disco.
+    pub stats: TransferStats,
+}
+
+/// Resultado do encerramento do servidor, retornado por `Server::run`.
+#[derive(Clone, Debug)]
+pub struct ShutdownSummary {
+    /// Transferências concluídas desde o início do servidor.
+    pub completed_transfers: u64,
+    /// Sessões encerradas por erro desde o início do servidor, incluindo as interrompidas pelo encerramento.
+    pub failed_transfers: u64,
+    /// Sessões que concluíram a transferência depois do pedido de encerramento.
+    pub drained_sessions: usize,
+    /// Sessões interrompidas pelo encerramento, antes de começar a transferência ou ao fim do prazo.
+    pub interrupted_sessions: usize,
+    /// Tempo entre o pedido de encerramento e o fim da última sessão.
+    pub drain_time: Duration,
+}
+
+impl ShutdownSummary {
+    /// Representação em JSON, numa única linha, com o tempo de encerramento em milissegundos.
+    pub fn to_json(&self) -> String {
+        format!(
+            concat!(
+                "{{\"completed_transfers\":{},\"failed_transfers\":{},\"drained_sessions\":{},",
+                "\"interrupted_sessions\":{},\"drain_time_ms\":{:.3}}}"
+            ),
+            self.completed_transfers,
+            self.failed_transfers,
+            self.drained_sessions,
+            self.interrupted_sessions,
+            self.drain_time.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+impl fmt::Display for ShutdownSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Servidor encerrado:")?;
+        writeln!(
+            f,
+            "  transferências concluídas: {}",
+            self.completed_transfers
+        )?;
+        writeln!(f, "  transferências com falha:  {}", self.failed_transfers)?;
+        writeln!(
+            f,
+            "  sessões no encerramento:   {} concluídas, {} interrompidas",
+            self.drained_sessions, self.interrupted_sessions
+        )?;
+        writeln!(
+            f,
+            "  tempo de encerramento:     {:.3} s",
+            self.drain_time.as_secs_f64()
+        )
+    }
+}
+
+/// Servidor que recebe arquivos de vários clientes simultaneamente.
+///
+/// ```no_run
+/// use servidor::{Server, ServerOptions};
+///
+/// Server::bind("[::]:5000", ServerOptions::default())?
+///     .on_file_received(|file| println!("{} recebido de {}", file.path.display(), file.peer))
+///     .run()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Server {
+    reactor: Reactor,
+    metrics_address: Option<SocketAddr>,
+}
+
+impl Server {
+    /// Faz o bind do socket TCP de escuta e, se configurado, inicia o endpoint de métricas. As conexões só são
+    /// aceitas a partir da chamada de `run`.
+    pub fn bind<A: ToSocketAddrs>(address: A, options: ServerOptions) -> Result<Server, Error> {
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Nenhum endereço para o bind"))?;
+        Server::bind_all(&[address], options)
+    }
+
+    /// Como `bind`, mas aceita conexões em todos os endereços informados.
+    pub fn bind_all(addresses: &[SocketAddr], options: ServerOptions) -> Result<Server, Error> {
+        if addresses.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Nenhum endereço para o bind",
+            ));
+        }
+        if options.shared_udp_port.is_none() && options.udp_port_range.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Faixa de portas UDP vazia",
+            ));
+        }
+
+        let metrics = Arc::new(Metrics::default());
+        let metrics_address = match options.metrics_address {
+            Some(metrics_address) => Some(metrics_endpoint::spawn(
+                metrics_address,
+                Arc::clone(&metrics),
+            )?),
+            None => None,
+        };
+
+        Ok(Server {
+            reactor: Reactor::new(addresses, options, metrics)?,
+            metrics_address,
+        })
+    }
+
+    /// Define a função chamada sempre que um arquivo termina de ser recebido e escrito em disco.
+    pub fn on_file_received<F>(mut self, callback: F) -> Server
+    where
+        F: FnMut(&ReceivedFile) + 'static,
+    {
+        self.reactor.callbacks.on_file_received = Some(Box::new(callback));
+        self
+    }
+
+    /// Define a função chamada quando uma sessão é encerrada por um erro, com o endereço do cliente.
+    pub fn on_session_error<F>(mut self, callback: F) -> Server
+    where
+        F: FnMut(SocketAddr, &GenericError) + 'static,
+    {
+        self.reactor.callbacks.on_session_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Define a função que cria o observador de cada sessão, notificado do andamento da transferência.
+    pub fn with_observer<F>(mut self, observer_factory: F) -> Server
+    where
+        F: FnMut() -> Box<dyn TransferObserver> + 'static,
+    {
+        self.reactor.callbacks.observer_factory = Some(Box::new(observer_factory));
+        self
+    }
+
+    /// Endereço em que o servidor aceita conexões; com vários endereços, o primeiro deles.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.reactor.local_addrs().map(|addresses| addresses[0])
+    }
+
+    /// Todos os endereços em que o servidor aceita conexões.
+    pub fn local_addrs(&self) -> Result<Vec<SocketAddr>, Error> {
+        self.reactor.local_addrs()
+    }
+
+    /// Permite trocar os parâmetros do servidor a partir de outra thread, enquanto `run` executa.
+    pub fn reload_handle(&self) -> ReloadHandle {
+        ReloadHandle {
+            pending: self.reactor.pending_reload(),
+        }
+    }
+
+    /// Permite pedir o encerramento do servidor a partir de outra thread, enquanto `run` executa.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            pending: self.reactor.pending_shutdown(),
+        }
+    }
+
+    /// Endereço do endpoint de métricas, caso ele tenha sido configurado.
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics_address
+    }
+
+    /// Executa o laço de eventos. Retorna depois de um pedido de encerramento feito por `ShutdownHandle`, quando não
+    /// restam sessões e as escritas em disco já terminaram, ou em caso de erro no poll.
+    pub fn run(mut self) -> Result<ShutdownSummary, Error> {
+        self.reactor.run()
+    }
+}
+
+/// Troca os parâmetros de um servidor em execução, como na releitura do arquivo de configuração.
+#[derive(Clone)]
+pub struct ReloadHandle {
+    pending: PendingReload,
+}
+
+impl ReloadHandle {
+    /// Entrega novos parâmetros ao servidor. Eles valem para as sessões abertas a partir de então; as sessões em
+    /// andamento terminam com os parâmetros com que começaram. `disk_workers`, `metrics_address`, `udp_port_range`
+    /// e `shared_udp_port` não podem ser alterados e são ignorados.
+    pub fn reload(&self, options: ServerOptions) -> Result<(), Error> {
+        self.pending.submit(options)
+    }
+}
+
+/// Encerra um servidor em execução, como no tratamento de SIGTERM.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    pending: PendingShutdown,
+}
+
+impl ShutdownHandle {
+    /// Para de aceitar conexões e interrompe as sessões que ainda não começaram a transferência. As demais têm até
+    /// `shutdown_grace` para terminar; as que não terminam a tempo são interrompidas, o cliente recebe o erro
+    /// `ShuttingDown` e o arquivo parcial é removido.
+    pub fn shutdown(&self) -> Result<(), Error> {
+        self.pending.submit(ShutdownMode::Graceful)
+    }
+
+    /// Como `shutdown`, mas interrompe imediatamente todas as sessões, mesmo depois de uma chamada a `shutdown`.
+    pub fn shutdown_now(&self) -> Result<(), Error> {
+        self.pending.submit(ShutdownMode::Immediate)
+    }
+}
@@ -1,21 +1,469 @@
-use std::env;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser};
+use serde::Deserialize;
+
+use common::logging::{self, LogFormat};
+use common::{parse_duration, MAX_CHUNK_SIZE};
+use servidor::{IpNetwork, ServerOptions};
+
+/// Recebe arquivos de vários clientes simultaneamente: o controle das transferências é feito via TCP e o conteúdo é
+/// recebido em blocos via UDP.
+///
+/// Os parâmetros podem vir de um arquivo TOML (`--config`), e os argumentos informados na linha de comando têm
+/// precedência sobre ele. O sinal SIGHUP relê o arquivo sem interromper as transferências em andamento. SIGTERM ou
+/// SIGINT encerram o servidor depois que as transferências em andamento terminam; um segundo sinal as interrompe.
+#[derive(Clone, Parser)]
+#[command(
+    name = "servidor",
+    version,
+    after_help = "Códigos de saída: 0 em caso de sucesso, 1 se o servidor não puder ser iniciado ou falhar e 2 para argumentos ou configuração inválidos."
+)]
+struct Args {
+    /// Arquivo de configuração TOML.
+    #[arg(long, value_name = "ARQUIVO")]
+    config: Option<PathBuf>,
+    /// Porta TCP em que as conexões são aceitas; substitui os endereços `listen` do arquivo de configuração.
+    port: Option<u16>,
+    /// Endereço IP em que as conexões são aceitas.
+    #[arg(long = "bind", value_name = "IP", requires = "port", default_value_t = Ipv6Addr::UNSPECIFIED.into())]
+    bind_address: IpAddr,
+    /// Quantidade máxima de blocos aceitos além do último bloco confirmado.
+    #[arg(long, value_name = "BLOCOS", default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..))]
+    window: u16,
+    /// Maior tamanho de bloco, em bytes, aceito dos clientes.
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = MAX_CHUNK_SIZE as u16,
+        value_parser = clap::value_parser!(u16).range(1..=MAX_CHUNK_SIZE as i64)
+    )]
+    chunk_size: u16,
+    /// Maior arquivo aceito dos clientes, em bytes, com sufixo opcional K, M, G ou T (potências de 1024).
+    #[arg(long, value_name = "TAMANHO", default_value = "10G", value_parser = parse_size)]
+    max_file_size: u64,
+    /// Diretório onde os arquivos recebidos são salvos, e de onde são servidos os downloads.
+    #[arg(long, value_name = "DIRETÓRIO", default_value = "output")]
+    output_dir: PathBuf,
+    /// Portas usadas pelos sockets UDP das sessões, no formato INÍCIO-FIM.
+    #[arg(long, value_name = "INÍCIO-FIM", default_value = "30000-39999", value_parser = parse_port_range)]
+    udp_port_range: RangeInclusive<u16>,
+    /// Recebe os blocos de todas as sessões numa única porta UDP, em vez de uma porta por sessão.
+    #[arg(
+        long,
+        value_name = "PORTA",
+        conflicts_with = "udp_port_range",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    shared_udp_port: Option<u16>,
+    /// Quantidade máxima de sessões simultâneas; conexões além dela aguardam na fila do sistema operacional.
+    #[arg(long, value_name = "N", default_value_t = 1024, value_parser = clap::value_parser!(u64).range(1..))]
+    max_sessions: u64,
+    /// Quantidade de threads que fazem a escrita dos arquivos em disco.
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u64).range(1..))]
+    disk_workers: u64,
+    /// Tempo máximo entre a conexão e o anúncio do arquivo, em segundos ou com sufixo ms, s ou m.
+    #[arg(long, value_name = "DURAÇÃO", default_value = "10s", value_parser = parse_duration)]
+    handshake_timeout: Duration,
+    /// Tempo máximo sem mensagens nem blocos do cliente durante a transferência.
+    #[arg(long, value_name = "DURAÇÃO", default_value = "30s", value_parser = parse_duration)]
+    idle_timeout: Duration,
+    /// Duração máxima de uma transferência, a partir do anúncio do arquivo; sem ele, não há limite.
+    #[arg(long, value_name = "DURAÇÃO", value_parser = parse_duration)]
+    transfer_timeout: Option<Duration>,
+    /// Tempo dado às transferências em andamento para terminar quando o servidor recebe SIGTERM ou SIGINT.
+    #[arg(long, value_name = "DURAÇÃO", default_value = "30s", value_parser = parse_duration)]
+    shutdown_grace: Duration,
+    /// Exige que os blocos UDP tragam o token enviado a cada cliente no início da sessão.
+    #[arg(long)]
+    require_token: bool,
+    /// Endereço do endpoint HTTP /metrics (exemplo: 0.0.0.0:9100); sem ele, as métricas não são expostas.
+    #[arg(long = "metrics", value_name = "ENDEREÇO")]
+    metrics_address: Option<SocketAddr>,
+    /// Registra mais detalhes: -v para o nível debug e -vv para o nível trace.
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count)]
+    verbosity: u8,
+    /// Formato das linhas de log (text ou json).
+    #[arg(long, value_name = "FORMATO", default_value = "text")]
+    log_format: LogFormat,
+    /// Imprime as estatísticas das transferências em JSON, em vez do resumo em texto.
+    #[arg(long)]
+    stats_json: bool,
+}
+
+/// Conteúdo do arquivo de configuração. Todas as chaves são opcionais; as ausentes usam o valor da linha de comando
+/// ou o padrão.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    server: ServerSection,
+    limits: LimitsSection,
+    auth: AuthSection,
+    logging: LoggingSection,
+    metrics: MetricsSection,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerSection {
+    listen: Option<Vec<String>>,
+    output_dir: Option<PathBuf>,
+    udp_port_range: Option<String>,
+    shared_udp_port: Option<u16>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LimitsSection {
+    max_sessions: Option<u64>,
+    disk_workers: Option<u64>,
+    window: Option<u16>,
+    max_chunk_size: Option<u16>,
+    max_file_size: Option<String>,
+    handshake_timeout: Option<String>,
+    idle_timeout: Option<String>,
+    transfer_timeout: Option<String>,
+    shutdown_grace: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AuthSection {
+    allowed_clients: Option<Vec<String>>,
+    require_token: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LoggingSection {
+    level: Option<String>,
+    format: Option<String>,
+    stats_json: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MetricsSection {
+    address: Option<String>,
+}
+
+/// Erro na leitura ou na validação da configuração, com a origem do valor problemático.
+#[derive(Debug)]
+pub struct ConfigError(String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Configuração do servidor, resultado da combinação da linha de comando com o arquivo de configuração.
 pub struct ServerConfig {
-    pub port: u16,
+    pub listen: Vec<SocketAddr>,
+    pub options: ServerOptions,
+    /// Quantidade de `--verbose`: 1 registra os eventos de nível `debug`, e 2 ou mais os de nível `trace`.
+    pub verbosity: u8,
+    /// Filtro de log usado sem `--verbose` nem `RUST_LOG`.
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// Imprime as estatísticas das transferências em JSON, em vez do resumo em texto.
+    pub stats_json: bool,
+    args: Args,
+    /// Argumentos informados explicitamente na linha de comando, que prevalecem sobre o arquivo.
+    explicit_args: HashSet<String>,
 }
 
 impl ServerConfig {
-    pub fn new(mut args: env::Args) -> Result<ServerConfig, &'static str> {
-        args.next();
+    /// Interpreta a linha de comando e, se indicado por `--config`, o arquivo de configuração. Argumentos inválidos
+    /// encerram o processo com a mensagem de uso do clap.
+    pub fn load() -> Result<ServerConfig, ConfigError> {
+        let matches = Args::command().get_matches();
+        let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        let explicit_args = matches
+            .ids()
+            .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+            .map(|id| id.to_string())
+            .collect();
+
+        ServerConfig::resolve(args, explicit_args)
+    }
+
+    /// Relê o arquivo de configuração, mantendo os argumentos da linha de comando.
+    pub fn reload(&self) -> Result<ServerConfig, ConfigError> {
+        ServerConfig::resolve(self.args.clone(), self.explicit_args.clone())
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.args.config.as_deref()
+    }
+
+    fn resolve(args: Args, explicit_args: HashSet<String>) -> Result<ServerConfig, ConfigError> {
+        let file = match &args.config {
+            Some(path) => read_file(path)?,
+            None => FileConfig::default(),
+        };
+        let context = |key: &str, message: String| match &args.config {
+            Some(path) => ConfigError(format!("{}: {}: {}", path.display(), key, message)),
+            None => ConfigError(format!("{}: {}", key, message)),
+        };
+        let explicit = |id: &str| explicit_args.contains(id);
+
+        let listen = match (&args.port, &file.server.listen) {
+            (Some(port), _) => vec![SocketAddr::new(args.bind_address, *port)],
+            (None, Some(addresses)) if !addresses.is_empty() => addresses
+                .iter()
+                .map(|address| {
+                    address.parse().map_err(|_e| {
+                        context(
+                            "server.listen",
+                            format!("endereço inválido \"{}\" (exemplo: \"[::]:5000\")", address),
+                        )
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            (None, Some(_empty)) => {
+                return Err(context(
+                    "server.listen",
+                    String::from("informe ao menos um endereço"),
+                ))
+            }
+            (None, None) => {
+                return Err(ConfigError(String::from(
+                    "Nenhuma porta especificada: informe a porta ou server.listen no arquivo de configuração",
+                )))
+            }
+        };
+
+        let output_dir = match (&file.server.output_dir, explicit("output_dir")) {
+            (Some(output_dir), false) => output_dir.clone(),
+            _ => args.output_dir.clone(),
+        };
+        let udp_port_range = match (&file.server.udp_port_range, explicit("udp_port_range")) {
+            (Some(range), false) => parse_port_range(range)
+                .map_err(|e| context("server.udp_port_range", e.to_string()))?,
+            _ => args.udp_port_range.clone(),
+        };
+        // Uma faixa informada na linha de comando desativa a porta compartilhada do arquivo, e vice-versa.
+        let shared_udp_port = if explicit("shared_udp_port") || explicit("udp_port_range") {
+            args.shared_udp_port
+        } else {
+            if file.server.udp_port_range.is_some() && file.server.shared_udp_port.is_some() {
+                return Err(context(
+                    "server.shared_udp_port",
+                    String::from("não pode ser usada junto com server.udp_port_range"),
+                ));
+            }
+            file.server.shared_udp_port
+        };
+        if shared_udp_port == Some(0) {
+            return Err(context(
+                "server.shared_udp_port",
+                String::from("deve ser maior que zero"),
+            ));
+        }
+
+        let max_sessions = pick(
+            explicit("max_sessions"),
+            args.max_sessions,
+            file.limits.max_sessions,
+        );
+        if max_sessions == 0 {
+            return Err(context(
+                "limits.max_sessions",
+                String::from("deve ser maior que zero"),
+            ));
+        }
+        let disk_workers = pick(
+            explicit("disk_workers"),
+            args.disk_workers,
+            file.limits.disk_workers,
+        );
+        if disk_workers == 0 {
+            return Err(context(
+                "limits.disk_workers",
+                String::from("deve ser maior que zero"),
+            ));
+        }
+        let window = pick(explicit("window"), args.window, file.limits.window);
+        if window == 0 {
+            return Err(context(
+                "limits.window",
+                String::from("deve ser maior que zero"),
+            ));
+        }
+        let chunk_size = pick(
+            explicit("chunk_size"),
+            args.chunk_size,
+            file.limits.max_chunk_size,
+        );
+        if chunk_size == 0 || chunk_size as usize > MAX_CHUNK_SIZE {
+            return Err(context(
+                "limits.max_chunk_size",
+                format!("deve estar entre 1 e {}", MAX_CHUNK_SIZE),
+            ));
+        }
+
+        let max_file_size = match (&file.limits.max_file_size, explicit("max_file_size")) {
+            (Some(size), false) => {
+                parse_size(size).map_err(|e| context("limits.max_file_size", e.to_string()))?
+            }
+            _ => args.max_file_size,
+        };
+
+        let file_duration = |key: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|value| parse_duration(value).map_err(|e| context(key, e.to_string())))
+                .transpose()
+        };
+        let handshake_timeout = pick(
+            explicit("handshake_timeout"),
+            args.handshake_timeout,
+            file_duration("limits.handshake_timeout", &file.limits.handshake_timeout)?,
+        );
+        let idle_timeout = pick(
+            explicit("idle_timeout"),
+            args.idle_timeout,
+            file_duration("limits.idle_timeout", &file.limits.idle_timeout)?,
+        );
+        let transfer_timeout = pick(
+            explicit("transfer_timeout"),
+            args.transfer_timeout,
+            file_duration("limits.transfer_timeout", &file.limits.transfer_timeout)?.map(Some),
+        );
+        let shutdown_grace = pick(
+            explicit("shutdown_grace"),
+            args.shutdown_grace,
+            file_duration("limits.shutdown_grace", &file.limits.shutdown_grace)?,
+        );
+
+        let allowed_clients = file
+            .auth
+            .allowed_clients
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|network| network.parse::<IpNetwork>())
+            .collect::<Result<_, _>>()
+            .map_err(|e| context("auth.allowed_clients", e))?;
+        let require_token = pick(
+            explicit("require_token"),
+            args.require_token,
+            file.auth.require_token,
+        );
 
-        let port = match args.next() {
-            Some(port) => port,
-            None => return Err("Nenhuma porta especificada"),
+        let metrics_address = match (&file.metrics.address, explicit("metrics_address")) {
+            (Some(address), false) => Some(address.parse().map_err(|_e| {
+                context(
+                    "metrics.address",
+                    format!(
+                        "endereço inválido \"{}\" (exemplo: \"0.0.0.0:9100\")",
+                        address
+                    ),
+                )
+            })?),
+            _ => args.metrics_address,
         };
 
-        let port = port
-            .parse()
-            .expect("A porta deve ser um inteiro unsigned de 16 bits");
+        let log_level = file
+            .logging
+            .level
+            .clone()
+            .unwrap_or_else(|| String::from("info"));
+        logging::validate_level(&log_level).map_err(|e| context("logging.level", e))?;
+        let log_format = match (&file.logging.format, explicit("log_format")) {
+            (Some(format), false) => format
+                .parse()
+                .map_err(|e: &str| context("logging.format", e.to_string()))?,
+            _ => args.log_format,
+        };
+        let stats_json = pick(
+            explicit("stats_json"),
+            args.stats_json,
+            file.logging.stats_json,
+        );
+
+        Ok(ServerConfig {
+            listen,
+            options: ServerOptions {
+                max_sessions: max_sessions as usize,
+                disk_workers: disk_workers as usize,
+                metrics_address,
+                receive_window: window,
+                max_chunk_size: chunk_size as usize,
+                max_file_size,
+                output_dir,
+                udp_port_range,
+                shared_udp_port,
+                allowed_clients,
+                handshake_timeout,
+                idle_timeout,
+                transfer_timeout,
+                shutdown_grace,
+                require_token,
+            },
+            verbosity: args.verbosity,
+            log_level,
+            log_format,
+            stats_json,
+            args,
+            explicit_args,
+        })
+    }
+}
+
+/// O valor da linha de comando, se informado explicitamente, senão o do arquivo, senão o padrão da linha de comando.
+fn pick<T>(explicit: bool, arg: T, file: Option<T>) -> T {
+    match file {
+        Some(value) if !explicit => value,
+        _ => arg,
+    }
+}
+
+fn read_file(path: &Path) -> Result<FileConfig, ConfigError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        ConfigError(format!(
+            "Falha ao ler o arquivo de configuração {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    toml::from_str(&contents).map_err(|e| ConfigError(format!("{}: {}", path.display(), e)))
+}
+
+/// Interpreta uma faixa de portas no formato "30000-39999", ou uma única porta.
+fn parse_port_range(range: &str) -> Result<RangeInclusive<u16>, &'static str> {
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None => (range.trim(), range.trim()),
+    };
+
+    match (start.parse::<u16>(), end.parse::<u16>()) {
+        (Ok(start), Ok(end)) if start > 0 && start <= end => Ok(start..=end),
+        _ => Err("Faixa de portas inválida (exemplo: 30000-39999)"),
+    }
+}
+
+/// Interpreta um tamanho em bytes, com sufixo opcional K, M, G ou T (potências de 1024; por exemplo, "10G").
+fn parse_size(size: &str) -> Result<u64, &'static str> {
+    let size = size.trim();
+    let (number, shift) = match size.chars().last() {
+        Some('k') | Some('K') => (&size[..size.len() - 1], 10),
+        Some('m') | Some('M') => (&size[..size.len() - 1], 20),
+        Some('g') | Some('G') => (&size[..size.len() - 1], 30),
+        Some('t') | Some('T') => (&size[..size.len() - 1], 40),
+        _ => (size, 0),
+    };
 
-        Ok(ServerConfig { port })
+    match number.parse::<u64>() {
+        Ok(value) if value > 0 && value.leading_zeros() >= shift => Ok(value << shift),
+        _ => Err("Tamanho inválido (exemplos: 500M, 10G)"),
     }
 }
@@ -0,0 +1,930 @@
+use std::ffi::OsStr;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use mio::net::{TcpStream, UdpSocket};
+use mio::{Interest, Registry, Token};
+use tracing::span::EnteredSpan;
+use tracing::{debug, info, info_span, trace, warn, Span};
+
+use common::{
+    parse_message, AckData, ChunkData, ConnectionData, ErrorCode, FileData, FileReceiver,
+    GenericError, GetData, Message, MessageCreationError, SendOptions, Sender, TransferInfo,
+    TransferObserver, TransferStats, MAX_DATAGRAM_SIZE,
+};
+
+use crate::disk_pool::{DiskEvent, DiskPool};
+use crate::metrics::{DropReason, Metrics};
+use crate::udp_port_pool::{UdpPortLease, UdpPorts};
+use crate::{ReceivedFile, ServerOptions};
+
+/// Quantos bytes do arquivo pedido ficam em memória durante o download, entre os blocos em trânsito e os lidos
+/// antecipadamente do disco. Também limita a janela de envio, já que os blocos em trânsito precisam ficar em memória
+/// até o ack.
+const READ_AHEAD_BYTES: usize = 1 << 20;
+
+/// Etapas de uma sessão, na ordem em que acontecem.
+enum SessionState {
+    AwaitingHello,
+    AwaitingInfoFile,
+    Receiving(FileReceiver),
+    /// Todos os blocos foram recebidos; aguardando o fim da escrita em disco.
+    Flushing,
+    /// O cliente pediu um arquivo com a mensagem "Get"; aguardando a abertura do arquivo.
+    Loading(GetData),
+    /// Enviando os blocos do arquivo pedido e recebendo os acks do cliente.
+    Sending(Box<Sender>),
+    /// A transferência terminou com a mensagem de fim; aguardando outro arquivo, com "InfoFile" ou "Get", ou o fim da
+    /// sessão.
+    Idle,
+    /// O cliente encerrou a sessão, com a mensagem "Close" ou fechando a conexão depois de uma transferência; ela é
+    /// removida assim que as mensagens pendentes forem enviadas.
+    Closing,
+}
+
+/// Uma transferência concluída pela sessão, a ser contabilizada pelo `Reactor`.
+pub struct CompletedTransfer {
+    pub stats: TransferStats,
+    /// O arquivo recebido, quando o cliente enviou o arquivo; ausente no download.
+    pub received_file: Option<ReceivedFile>,
+}
+
+/// Uma conexão de um cliente: o socket TCP de controle, o socket UDP de dados e o estado da transferência.
+///
+/// Os sockets são não bloqueantes e os métodos são chamados pelo laço de eventos quando há dados disponíveis.
+pub struct Session {
+    id: usize,
+    peer: SocketAddr,
+    stream: TcpStream,
+    udp_lease: Option<UdpPortLease>,
+    /// Endereço de origem do primeiro bloco aceito; a partir dele, os blocos de outras origens são descartados. No
+    /// download, é o endereço para o qual os blocos são enviados.
+    data_peer: Option<SocketAddr>,
+    /// Se o socket UDP aceita novos datagramas; deixa de aceitar quando um envio retorna `WouldBlock`.
+    data_writable: bool,
+    /// Token enviado ao cliente na mensagem "Connection" e exigido nos blocos, quando `require_token` está ativo.
+    token: u64,
+    state: SessionState,
+    /// Arquivo anunciado pelo cliente na mensagem InfoFile, ou anunciado ao cliente no download.
+    file: Option<FileData>,
+    /// Bytes lidos do socket TCP que ainda não formam uma mensagem completa.
+    received_bytes: Vec<u8>,
+    /// Bytes a serem enviados pelo socket TCP, quando ele voltar a aceitar escrita.
+    pending_output: Vec<u8>,
+    /// Blocos enfileirados para escrita em disco e ainda não escritos.
+    pending_writes: Arc<AtomicUsize>,
+    /// Blocos do arquivo pedido cuja leitura já foi pedida ao `DiskPool`, no download.
+    requested_chunks: u32,
+    observer: Box<dyn TransferObserver>,
+    options: Arc<ServerOptions>,
+    metrics: Arc<Metrics>,
+    stats: TransferStats,
+    /// Transferência concluída ainda não contabilizada pelo `Reactor`.
+    completed_transfer: Option<CompletedTransfer>,
+    accepted_at: Instant,
+    /// Última mensagem ou bloco aceito do cliente, para o prazo de inatividade.
+    last_activity: Instant,
+    /// Início da transferência, quando o arquivo é anunciado pelo cliente.
+    started_at: Option<Instant>,
+    /// O cliente pausou o envio dos blocos; enquanto isso, o prazo de inatividade não se aplica.
+    paused: bool,
+    /// Remove o arquivo parcial ao encerrar a sessão antes do fim da transferência, em vez de mantê-lo.
+    discard_partial_file: bool,
+    /// Contexto dos eventos registrados durante a sessão: identificador e endereço do cliente.
+    span: Span,
+    /// Contexto da transferência em andamento, filho de `span`, com o nome do arquivo.
+    transfer_span: Option<Span>,
+}
+
+impl Session {
+    pub fn new(
+        id: usize,
+        peer: SocketAddr,
+        stream: TcpStream,
+        observer: Box<dyn TransferObserver>,
+        options: Arc<ServerOptions>,
+        metrics: Arc<Metrics>,
+    ) -> Session {
+        Session {
+            id,
+            peer,
+            stream,
+            udp_lease: None,
+            data_peer: None,
+            data_writable: true,
+            token: 0,
+            state: SessionState::AwaitingHello,
+            file: None,
+            received_bytes: Vec::new(),
+            pending_output: Vec::new(),
+            pending_writes: Arc::new(AtomicUsize::new(0)),
+            requested_chunks: 0,
+            observer,
+            options,
+            metrics,
+            stats: TransferStats::default(),
+            completed_transfer: None,
+            accepted_at: Instant::now(),
+            last_activity: Instant::now(),
+            started_at: None,
+            paused: false,
+            discard_partial_file: false,
+            span: info_span!("session", id, peer = %peer),
+            transfer_span: None,
+        }
+    }
+
+    pub fn control_token(id: usize) -> Token {
+        Token(id * 2)
+    }
+
+    pub fn data_token(id: usize) -> Token {
+        Token(id * 2 + 1)
+    }
+
+    pub fn register(&mut self, registry: &Registry) -> Result<(), Error> {
+        // Os acks são mensagens pequenas e não podem esperar pelo algoritmo de Nagle, principalmente nas rajadas em
+        // que o cliente retoma o envio depois de uma pausa.
+        self.stream.set_nodelay(true)?;
+        registry.register(
+            &mut self.stream,
+            Session::control_token(self.id),
+            Interest::READABLE | Interest::WRITABLE,
+        )
+    }
+
+    /// Se há uma transferência em andamento, ou seja, se o cliente já anunciou ou pediu um arquivo que ainda não foi
+    /// concluído.
+    pub fn has_started_transfer(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    /// Se a sessão já concluiu uma transferência e aguarda o próximo arquivo.
+    pub fn is_idle(&self) -> bool {
+        matches!(self.state, SessionState::Idle)
+    }
+
+    /// A sessão terminou e pode ser removida.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, SessionState::Closing) && self.pending_output.is_empty()
+    }
+
+    /// Instante em que a sessão expira caso o cliente não avance, e o motivo informado a ele nesse caso. Enquanto os
+    /// blocos são escritos ou o arquivo pedido é lido, o cliente não tem o que fazer e a sessão não expira; enquanto a
+    /// transferência está pausada, só a duração máxima da transferência se aplica.
+    pub fn deadline(&self) -> Option<(Instant, ErrorCode)> {
+        let idle = (
+            self.last_activity + self.options.idle_timeout,
+            ErrorCode::IdleTimeout,
+        );
+        match self.state {
+            SessionState::AwaitingHello | SessionState::AwaitingInfoFile => Some((
+                self.accepted_at + self.options.handshake_timeout,
+                ErrorCode::HandshakeTimeout,
+            )),
+            SessionState::Receiving(_) | SessionState::Sending(_) => {
+                let transfer = self.started_at.zip(self.options.transfer_timeout).map(
+                    |(started_at, timeout)| (started_at + timeout, ErrorCode::TransferTimeout),
+                );
+                match transfer {
+                    Some(transfer) if self.paused || transfer.0 < idle.0 => Some(transfer),
+                    _ if self.paused => None,
+                    _ => Some(idle),
+                }
+            }
+            SessionState::Flushing | SessionState::Loading(_) => None,
+            SessionState::Idle | SessionState::Closing => Some(idle),
+        }
+    }
+
+    /// Próximo instante em que o envio dos blocos precisa ser retomado, pelo pacer ou pela retransmissão, quando a
+    /// sessão envia um arquivo.
+    pub fn next_timer(&self) -> Option<Instant> {
+        match &self.state {
+            SessionState::Sending(sender) if self.data_writable => sender.next_deadline(),
+            _ => None,
+        }
+    }
+
+    /// Se o último envio de blocos foi bloqueado porque o socket UDP não aceitava mais datagramas.
+    pub fn is_blocked_on_send(&self) -> bool {
+        !self.data_writable
+    }
+
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Contexto dos eventos da sessão, ou da transferência em andamento, caso haja uma.
+    pub fn span(&self) -> &Span {
+        self.transfer_span.as_ref().unwrap_or(&self.span)
+    }
+
+    /// A transferência concluída desde a última chamada, caso haja uma.
+    pub fn take_completed_transfer(&mut self) -> Option<CompletedTransfer> {
+        self.completed_transfer.take()
+    }
+
+    /// Trata um evento do socket TCP: envia os bytes pendentes e processa as mensagens recebidas.
+    pub fn on_control_event(
+        &mut self,
+        registry: &Registry,
+        disk_pool: &DiskPool,
+        udp_ports: &mut UdpPorts,
+    ) -> Result<(), GenericError> {
+        GenericError::transform_io(self.flush())?;
+
+        let connection_closed = GenericError::transform_io(self.read_available())?;
+
+        while let Some((message, length)) =
+            GenericError::transform_logic(parse_message(&self.received_bytes))?
+        {
+            self.received_bytes.drain(..length);
+            self.last_activity = Instant::now();
+            self.on_control_message(message, registry, disk_pool, udp_ports)?;
+        }
+
+        if connection_closed {
+            match self.state {
+                // Clientes que enviam um único arquivo fecham a conexão depois da mensagem de fim, sem "Close".
+                SessionState::Idle => self.state = SessionState::Closing,
+                SessionState::Closing => {}
+                _ => {
+                    return Err(GenericError::IO(Error::new(
+                        ErrorKind::ConnectionAborted,
+                        "Conexão fechada",
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_control_message(
+        &mut self,
+        message: Message,
+        registry: &Registry,
+        disk_pool: &DiskPool,
+        udp_ports: &mut UdpPorts,
+    ) -> Result<(), GenericError> {
+        match (&self.state, message) {
+            (_, Message::Abort) => {
+                self.discard_partial_file();
+                Err(GenericError::IO(Error::new(
+                    ErrorKind::ConnectionAborted,
+                    "Transferência cancelada pelo cliente",
+                )))
+            }
+            (SessionState::Receiving(_), Message::Pause) => {
+                if !self.paused {
+                    info!("Transferência pausada pelo cliente");
+                    self.paused = true;
+                }
+                Ok(())
+            }
+            (SessionState::Receiving(_), Message::Resume) => {
+                if self.paused {
+                    info!("Transferência retomada pelo cliente");
+                    self.paused = false;
+                }
+                Ok(())
+            }
+            // O cliente pode pausar enquanto o último bloco ainda está a caminho, então a pausa é ignorada fora da
+            // recepção dos blocos.
+            (_, Message::Pause) | (_, Message::Resume) => Ok(()),
+            (SessionState::AwaitingHello, _hello) => {
+                let port = match udp_ports {
+                    UdpPorts::Pool(pool) => {
+                        let mut lease = pool.lease().map_err(|e| {
+                            warn!("{}", e);
+                            GenericError::Protocol(ErrorCode::NoUdpPortAvailable)
+                        })?;
+                        let port = lease.port;
+
+                        let registered = registry.register(
+                            &mut lease.socket,
+                            Session::data_token(self.id),
+                            Interest::READABLE | Interest::WRITABLE,
+                        );
+                        if let Err(e) = registered {
+                            pool.release(lease);
+                            return Err(GenericError::IO(e));
+                        }
+                        self.udp_lease = Some(lease);
+                        port
+                    }
+                    UdpPorts::Shared { port, .. } => *port,
+                };
+                debug!(udp_port = port, "Usará UDP na porta");
+
+                if self.options.require_token {
+                    self.token = GenericError::transform_io(getrandom::u64().map_err(Error::from))?;
+                }
+
+                self.state = SessionState::AwaitingInfoFile;
+                self.send(&Message::Connection(ConnectionData {
+                    port: port as u32,
+                    receive_window: self.options.receive_window,
+                    session_id: self.id as u32,
+                    token: self.token,
+                }))
+            }
+            (SessionState::AwaitingInfoFile, Message::Close)
+            | (SessionState::Idle, Message::Close) => {
+                debug!("Sessão encerrada pelo cliente");
+                self.state = SessionState::Closing;
+                Ok(())
+            }
+            (SessionState::AwaitingInfoFile, Message::InfoFile(file_data))
+            | (SessionState::Idle, Message::InfoFile(file_data))
+            | (SessionState::AwaitingInfoFile, Message::InfoPath(file_data))
+            | (SessionState::Idle, Message::InfoPath(file_data)) => {
+                let _entered = self.enter_transfer_span(&file_data.filename);
+                let chunk_size = file_data.chunk_size as usize;
+                if chunk_size == 0 || chunk_size > self.options.max_chunk_size {
+                    return Err(GenericError::Logic(MessageCreationError::new(
+                        "Tamanho de bloco não suportado",
+                    )));
+                }
+                // Verificado antes de qualquer alocação: o `FileReceiver` só guarda a janela, mas os números de
+                // sequência têm 32 bits e não bastam para arquivos com blocos demais.
+                let expected_chunks =
+                    FileReceiver::expected_chunks(file_data.file_size, file_data.chunk_size);
+                if file_data.file_size > self.options.max_file_size
+                    || expected_chunks > u32::MAX as u64
+                {
+                    warn!(
+                        file_size = file_data.file_size,
+                        max_file_size = self.options.max_file_size,
+                        "Arquivo anunciado maior que o permitido"
+                    );
+                    return Err(GenericError::Protocol(ErrorCode::FileTooLarge));
+                }
+                let path = match output_path(&self.options.output_dir, &file_data.filename) {
+                    Some(path) => path,
+                    None => {
+                        warn!("Caminho de arquivo fora do diretório de saída");
+                        return Err(GenericError::Protocol(ErrorCode::InvalidPath));
+                    }
+                };
+                info!(
+                    file_size = file_data.file_size,
+                    chunk_size, "Começando a receber o arquivo"
+                );
+                disk_pool.create(self.id, path, Arc::clone(&self.pending_writes));
+
+                self.state = SessionState::Receiving(FileReceiver::new(
+                    file_data.file_size,
+                    file_data.chunk_size,
+                    self.options.receive_window,
+                ));
+                self.started_at = Some(Instant::now());
+                self.stats.file_size = file_data.file_size;
+                self.observer.on_handshake(&TransferInfo {
+                    peer: self.peer,
+                    filename: file_data.filename.clone(),
+                    file_size: file_data.file_size,
+                });
+                self.file = Some(file_data);
+                self.send(&Message::Ok)
+            }
+            (SessionState::AwaitingInfoFile, Message::Get(request))
+            | (SessionState::Idle, Message::Get(request)) => {
+                let _entered = self.enter_transfer_span(&request.filename);
+                let chunk_size = request.chunk_size as usize;
+                if chunk_size == 0 || chunk_size > self.options.max_chunk_size {
+                    return Err(GenericError::Logic(MessageCreationError::new(
+                        "Tamanho de bloco não suportado",
+                    )));
+                }
+                if request.receive_window == 0 {
+                    return Err(GenericError::Logic(MessageCreationError::new(
+                        "Janela de recepção vazia",
+                    )));
+                }
+                // Só são servidos arquivos da raiz do diretório de saída; os recebidos em subdiretórios, com
+                // "InfoPath", não podem ser pedidos, já que o nome da mensagem "Get" não tem diretórios.
+                if Path::new(&request.filename).file_name() != Some(OsStr::new(&request.filename)) {
+                    warn!("Nome de arquivo inválido no pedido de download");
+                    return Err(GenericError::Protocol(ErrorCode::FileNotFound));
+                }
+
+                info!(chunk_size, "Abrindo o arquivo pedido pelo cliente");
+                disk_pool.open(self.id, self.options.output_dir.join(&request.filename));
+                self.data_peer = Some(SocketAddr::new(self.peer.ip(), request.port));
+                self.started_at = Some(Instant::now());
+                self.state = SessionState::Loading(request);
+                Ok(())
+            }
+            (SessionState::Sending(_), Message::Ack(ack)) => {
+                self.advance_sender(Some(ack), &*udp_ports)?;
+                self.request_chunks(disk_pool);
+                Ok(())
+            }
+            (SessionState::Sending(sender), Message::End) if sender.is_complete() => {
+                self.stats = sender.stats();
+                info!(
+                    elapsed = ?self.stats.elapsed,
+                    chunks_sent = self.stats.chunks_sent,
+                    retransmissions = self.stats.retransmissions,
+                    "Arquivo enviado e confirmado pelo cliente"
+                );
+                self.observer.on_complete();
+                disk_pool.close(self.id);
+                self.complete_transfer(None);
+                Ok(())
+            }
+            _ => Err(GenericError::Logic(MessageCreationError::new(
+                "Tipo de mensagem inesperado",
+            ))),
+        }
+    }
+
+    /// Processa os datagramas disponíveis no socket UDP próprio da sessão, usado quando a porta não é compartilhada.
+    pub fn on_data_readable(&mut self, disk_pool: &DiskPool) -> Result<(), GenericError> {
+        let mut buffer = [0; MAX_DATAGRAM_SIZE];
+        loop {
+            let udp_socket = match &self.udp_lease {
+                Some(lease) => &lease.socket,
+                None => return Ok(()),
+            };
+
+            let (bytes_read, source) = match udp_socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(GenericError::IO(e)),
+            };
+            // Datagramas de terceiros são descartados antes da interpretação, para que não possam encerrar a sessão.
+            if !self.accepts_source(source) {
+                continue;
+            }
+
+            // Como no socket compartilhado, datagramas inválidos são apenas descartados: o token só é verificado em
+            // `on_chunk`, então qualquer datagrama vindo do endereço do cliente chega até aqui.
+            let chunk = match Message::new(&buffer, bytes_read) {
+                Ok(Message::File(chunk)) => chunk,
+                _ => {
+                    debug!(%source, "Datagrama inválido descartado");
+                    self.metrics.record_drop(DropReason::Malformed);
+                    continue;
+                }
+            };
+            if chunk.session_id as usize != self.id {
+                self.metrics.record_drop(DropReason::Unexpected);
+                continue;
+            }
+            self.on_chunk(chunk, source, disk_pool)?;
+        }
+    }
+
+    /// Processa um bloco do arquivo, recebido no socket UDP da sessão ou distribuído pelo `Reactor` a partir do socket
+    /// compartilhado.
+    pub fn on_chunk(
+        &mut self,
+        chunk: ChunkData,
+        source: SocketAddr,
+        disk_pool: &DiskPool,
+    ) -> Result<(), GenericError> {
+        if !self.accepts_source(source) {
+            return Ok(());
+        }
+        if self.options.require_token && chunk.token != self.token {
+            debug!(%source, "Bloco com token inválido descartado");
+            self.metrics.record_drop(DropReason::InvalidToken);
+            return Ok(());
+        }
+        if self.data_peer.is_none() {
+            debug!(%source, "Recebendo os blocos deste endereço");
+            self.data_peer = Some(source);
+        }
+        self.last_activity = Instant::now();
+
+        let ChunkData {
+            sequence_number,
+            data,
+            ..
+        } = chunk;
+        self.metrics.add_bytes_received(data.len());
+        trace!(
+            chunk = sequence_number,
+            bytes = data.len(),
+            "Bloco recebido"
+        );
+
+        let receiver = match &mut self.state {
+            SessionState::Receiving(receiver) => receiver,
+            _ => {
+                self.metrics.record_drop(DropReason::Unexpected);
+                return Ok(());
+            }
+        };
+        let outcome = receiver.on_chunk(sequence_number, data);
+        self.stats.chunks_received += 1;
+        if outcome.duplicate {
+            self.stats.duplicates_received += 1;
+            self.metrics.record_duplicate();
+            self.observer.on_retransmit(sequence_number);
+        }
+        if outcome.dropped {
+            self.stats.out_of_window_drops += 1;
+            self.metrics.record_drop(DropReason::OutOfWindow);
+        }
+        if outcome.malformed {
+            self.metrics.record_drop(DropReason::Malformed);
+        }
+
+        for chunk in outcome.ready_to_write {
+            disk_pool.write(self.id, chunk, &self.pending_writes);
+        }
+        for ack in outcome.acks {
+            let receive_window = self.advertised_window();
+            self.send(&Message::Ack(AckData {
+                sequence_number: ack,
+                receive_window,
+            }))?;
+            self.stats.acks += 1;
+            self.observer.on_chunk_acked(ack, self.acked_bytes(ack));
+        }
+
+        if outcome.finished {
+            debug!("Último ack enviado, finalizando");
+            disk_pool.finish(self.id);
+            self.state = SessionState::Flushing;
+        }
+
+        Ok(())
+    }
+
+    /// Trata o resultado de uma operação de disco da sessão.
+    pub fn on_disk_event(
+        &mut self,
+        event: DiskEvent,
+        disk_pool: &DiskPool,
+        udp_ports: &UdpPorts,
+    ) -> Result<(), GenericError> {
+        match event {
+            DiskEvent::Finished { .. } => {
+                if let Some(started_at) = self.started_at {
+                    self.stats.elapsed = started_at.elapsed();
+                }
+                info!(
+                    elapsed = ?self.stats.elapsed,
+                    chunks_received = self.stats.chunks_received,
+                    duplicates_received = self.stats.duplicates_received,
+                    out_of_window_drops = self.stats.out_of_window_drops,
+                    "Arquivo recebido, enviando mensagem de fim de transmissão."
+                );
+                let received_file = self.file.as_ref().map(|file| ReceivedFile {
+                    peer: self.peer,
+                    path: self.options.output_dir.join(&file.filename),
+                    file_size: file.file_size,
+                    stats: self.stats.clone(),
+                });
+                self.observer.on_complete();
+                self.complete_transfer(received_file);
+                self.send(&Message::End)
+            }
+            DiskEvent::Opened { file_size, .. } => {
+                self.start_sending(file_size, disk_pool, udp_ports)
+            }
+            DiskEvent::ChunksRead { chunks, .. } => {
+                if let SessionState::Sending(sender) = &mut self.state {
+                    sender.push_chunks(chunks);
+                }
+                self.advance_sender(None, udp_ports)
+            }
+            DiskEvent::Failed { error, .. } if matches!(self.state, SessionState::Loading(_)) => {
+                warn!("Falha ao ler o arquivo pedido: {}", error);
+                Err(GenericError::Protocol(ErrorCode::FileNotFound))
+            }
+            DiskEvent::Failed { error, .. } => Err(GenericError::IO(error)),
+        }
+    }
+
+    /// Anuncia ao cliente o arquivo pedido, com a mensagem "InfoFile", e começa a ler e enviar os blocos.
+    fn start_sending(
+        &mut self,
+        file_size: u64,
+        disk_pool: &DiskPool,
+        udp_ports: &UdpPorts,
+    ) -> Result<(), GenericError> {
+        let request = match &self.state {
+            SessionState::Loading(request) => request,
+            _ => return Ok(()),
+        };
+        if FileReceiver::expected_chunks(file_size, request.chunk_size) > u32::MAX as u64 {
+            warn!(
+                file_size,
+                "Arquivo pedido tem blocos demais para o tamanho de bloco pedido"
+            );
+            return Err(GenericError::Protocol(ErrorCode::FileTooLarge));
+        }
+        let file_data = FileData {
+            filename: request.filename.clone(),
+            file_size,
+            chunk_size: request.chunk_size,
+        };
+        let connection = ConnectionData {
+            port: request.port as u32,
+            receive_window: request.receive_window,
+            session_id: self.id as u32,
+            token: self.token,
+        };
+        let options = SendOptions {
+            chunk_size: request.chunk_size as usize,
+            window: Some(read_ahead_chunks(request.chunk_size)),
+            ..SendOptions::default()
+        };
+        info!(
+            file_size = file_data.file_size,
+            chunk_size = file_data.chunk_size,
+            "Começando a enviar o arquivo"
+        );
+
+        self.state =
+            SessionState::Sending(Box::new(Sender::streamed(file_size, &connection, &options)));
+        self.requested_chunks = 0;
+        self.stats.file_size = file_data.file_size;
+        self.observer.on_handshake(&TransferInfo {
+            peer: self.peer,
+            filename: file_data.filename.clone(),
+            file_size: file_data.file_size,
+        });
+        self.send(&Message::InfoFile(FileData {
+            filename: file_data.filename.clone(),
+            ..file_data
+        }))?;
+        self.file = Some(file_data);
+        self.request_chunks(disk_pool);
+        self.advance_sender(None, udp_ports)
+    }
+
+    /// Pede ao `DiskPool` a leitura dos próximos blocos do arquivo enviado, mantendo em memória até
+    /// `READ_AHEAD_BYTES` a partir do primeiro bloco não confirmado. As leituras são agrupadas, para não gerar uma
+    /// operação de disco por ack.
+    fn request_chunks(&mut self, disk_pool: &DiskPool) {
+        let (sender, chunk_size) = match (&self.state, &self.file) {
+            (SessionState::Sending(sender), Some(file)) => (sender, file.chunk_size),
+            _ => return,
+        };
+        let read_ahead = read_ahead_chunks(chunk_size) as u32;
+        let target = sender
+            .send_base()
+            .saturating_add(read_ahead)
+            .min(sender.chunk_count());
+        let count = target.saturating_sub(self.requested_chunks);
+        let batch = (read_ahead / 4).max(1);
+        if count == 0 || (count < batch && target < sender.chunk_count()) {
+            return;
+        }
+
+        trace!(
+            first_chunk = self.requested_chunks,
+            count,
+            "Lendo blocos do arquivo pedido"
+        );
+        disk_pool.read(
+            self.id,
+            self.requested_chunks as u64 * chunk_size as u64,
+            chunk_size as usize,
+            count as usize,
+        );
+        self.requested_chunks = target;
+    }
+
+    /// Cria o contexto da transferência do arquivo `filename` e entra nele, para os eventos registrados até o fim do
+    /// tratamento da mensagem atual; nos eventos seguintes, ele é usado pelo `Reactor` por meio de `span`.
+    fn enter_transfer_span(&mut self, filename: &str) -> EnteredSpan {
+        let span = info_span!(parent: &self.span, "transfer", file = %filename);
+        self.transfer_span = Some(span.clone());
+        span.entered()
+    }
+
+    /// Guarda a transferência concluída para o `Reactor` e volta a aguardar outro arquivo na mesma sessão, com a mesma
+    /// porta UDP.
+    fn complete_transfer(&mut self, received_file: Option<ReceivedFile>) {
+        self.completed_transfer = Some(CompletedTransfer {
+            stats: std::mem::take(&mut self.stats),
+            received_file,
+        });
+        self.state = SessionState::Idle;
+        self.transfer_span = None;
+        self.file = None;
+        self.started_at = None;
+        self.paused = false;
+        // O próximo arquivo pode vir de outro socket do cliente.
+        self.data_peer = None;
+        self.data_writable = true;
+    }
+
+    /// Retoma o envio dos blocos quando o socket UDP volta a aceitar datagramas.
+    pub fn on_data_writable(&mut self, udp_ports: &UdpPorts) -> Result<(), GenericError> {
+        self.data_writable = true;
+        self.advance_sender(None, udp_ports)
+    }
+
+    /// Retransmite os blocos ou envia os permitidos pelo pacer, quando o prazo de `next_timer` é atingido.
+    pub fn on_timer(&mut self, udp_ports: &UdpPorts) -> Result<(), GenericError> {
+        self.advance_sender(None, udp_ports)
+    }
+
+    /// Avança o envio dos blocos do arquivo pedido: processa o ack recebido, se houver, retransmite os blocos caso o
+    /// temporizador tenha expirado e envia os blocos permitidos pela janela e pelo pacer.
+    fn advance_sender(
+        &mut self,
+        ack: Option<AckData>,
+        udp_ports: &UdpPorts,
+    ) -> Result<(), GenericError> {
+        let sender = match &mut self.state {
+            SessionState::Sending(sender) => sender,
+            _ => return Ok(()),
+        };
+        let socket: &UdpSocket = match (&self.udp_lease, udp_ports) {
+            (Some(lease), _) => &lease.socket,
+            (None, UdpPorts::Shared { socket, .. }) => socket,
+            (None, UdpPorts::Pool(_)) => return Ok(()),
+        };
+        let destination = match self.data_peer {
+            Some(destination) => destination,
+            None => return Ok(()),
+        };
+        let mut transmit = |data: &[u8]| socket.send_to(data, destination).map(|_bytes_sent| ());
+
+        let writable = self.data_writable;
+        let result = ack
+            .map_or(Ok(()), |ack| sender.on_ack(ack, &mut transmit))
+            .and_then(|()| {
+                if writable {
+                    sender.on_timer(&mut transmit)?;
+                    sender.send_ready_chunks(&mut transmit)
+                } else {
+                    Ok(())
+                }
+            });
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                self.data_writable = false;
+                Ok(())
+            }
+            Err(e) => Err(GenericError::IO(e)),
+        }
+    }
+
+    /// Notifica o observador de que a sessão será encerrada por um erro. Erros de protocolo também são comunicados ao
+    /// cliente, sem esperar pelo envio, já que a conexão será fechada em seguida.
+    pub fn on_error(&mut self, error: &GenericError) {
+        if let GenericError::Protocol(code) = error {
+            self.notify(*code);
+        }
+        self.observer.on_error(error);
+    }
+
+    /// Avisa o cliente com uma mensagem de erro, sem notificar o observador, como ao encerrar uma sessão ociosa.
+    pub fn notify(&mut self, code: ErrorCode) {
+        let _ = self.send(&Message::Error(code));
+    }
+
+    /// Faz com que o arquivo parcial seja removido caso a sessão seja encerrada antes do fim da transferência.
+    pub fn discard_partial_file(&mut self) {
+        self.discard_partial_file = true;
+    }
+
+    /// Remove os sockets do poll e fecha o arquivo, caso a transferência não tenha terminado.
+    pub fn close(mut self, registry: &Registry, disk_pool: &DiskPool, udp_ports: &mut UdpPorts) {
+        if !matches!(self.state, SessionState::Idle | SessionState::Closing) {
+            if self.discard_partial_file {
+                disk_pool.discard(self.id);
+            } else {
+                disk_pool.close(self.id);
+            }
+        }
+        if let Some(mut lease) = self.udp_lease.take() {
+            let _ = registry.deregister(&mut lease.socket);
+            udp_ports.release(lease);
+        }
+        let _ = registry.deregister(&mut self.stream);
+    }
+
+    /// Se um datagrama de `source` pode pertencer à sessão: ele deve vir do mesmo IP da conexão TCP e, depois do
+    /// primeiro bloco aceito, do mesmo endereço que ele. Os demais são contados como descartados.
+    fn accepts_source(&self, source: SocketAddr) -> bool {
+        let accepted = match self.data_peer {
+            Some(data_peer) => source == data_peer,
+            None => source.ip().to_canonical() == self.peer.ip().to_canonical(),
+        };
+        if !accepted {
+            debug!(%source, "Datagrama de origem desconhecida descartado");
+            self.metrics.record_drop(DropReason::ForeignSource);
+        }
+        accepted
+    }
+
+    /// Janela anunciada ao cliente: o espaço livre na fila de escrita em disco, e no mínimo um bloco, para que o
+    /// cliente continue enviando e receba as atualizações da janela.
+    fn advertised_window(&self) -> u16 {
+        let pending = self.pending_writes.load(Ordering::SeqCst);
+        let free = (self.options.receive_window as usize).saturating_sub(pending);
+        free.max(1) as u16
+    }
+
+    /// Bytes do arquivo confirmados por um ack cumulativo até o bloco `ack`.
+    fn acked_bytes(&self, ack: u32) -> u64 {
+        match &self.file {
+            Some(file) => ((ack as u64 + 1) * file.chunk_size as u64).min(file.file_size),
+            None => 0,
+        }
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), GenericError> {
+        self.pending_output.extend(message.encode());
+        GenericError::transform_io(self.flush())
+    }
+
+    /// Envia os bytes pendentes até que o socket deixe de aceitar escrita.
+    fn flush(&mut self) -> Result<(), Error> {
+        while !self.pending_output.is_empty() {
+            match self.stream.write(&self.pending_output) {
+                Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "Conexão fechada")),
+                Ok(bytes_written) => {
+                    self.pending_output.drain(..bytes_written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lê todos os bytes disponíveis no socket TCP. Retorna se a conexão foi fechada pelo cliente.
+    fn read_available(&mut self) -> Result<bool, Error> {
+        let mut buffer = [0; 1024];
+
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Ok(true),
+                Ok(bytes_read) => self.received_bytes.extend_from_slice(&buffer[..bytes_read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Caminho em que o arquivo anunciado pelo cliente é salvo. O nome, ou o caminho relativo da mensagem "InfoPath", não
+/// pode ser absoluto nem conter "..", para que o arquivo fique dentro do diretório de saída.
+fn output_path(output_dir: &Path, relative_path: &str) -> Option<PathBuf> {
+    let path = Path::new(relative_path);
+    let inside_output_dir = !relative_path.is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if inside_output_dir {
+        Some(output_dir.join(path))
+    } else {
+        None
+    }
+}
+
+/// Quantidade de blocos de `chunk_size` bytes que cabem em `READ_AHEAD_BYTES`.
+fn read_ahead_chunks(chunk_size: u16) -> u16 {
+    (READ_AHEAD_BYTES / chunk_size.max(1) as usize).clamp(1, u16::MAX as usize) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::output_path;
+
+    #[test]
+    fn output_path_accepts_relative_paths() {
+        let output_dir = Path::new("/srv/files");
+        assert_eq!(output_path(output_dir, "x"), Some(output_dir.join("x")));
+        assert_eq!(
+            output_path(output_dir, "a/b/x"),
+            Some(output_dir.join("a/b/x"))
+        );
+        assert_eq!(
+            output_path(output_dir, "a//b"),
+            Some(output_dir.join("a/b"))
+        );
+    }
+
+    #[test]
+    fn output_path_rejects_paths_outside_the_output_dir() {
+        let output_dir = Path::new("/srv/files");
+        for path in ["../x", "a/../../x", "a/../x", "/etc/x", "", "./x"] {
+            assert_eq!(output_path(output_dir, path), None, "{:?}", path);
+        }
+    }
+}
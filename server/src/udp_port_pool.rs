@@ -0,0 +1,191 @@
+use std::io::{Error, ErrorKind};
+use std::net::{Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use mio::net::UdpSocket;
+use tracing::debug;
+
+use crate::metrics::Metrics;
+
+/// Socket UDP aberto numa porta emprestada pelo `UdpPortPool`, que deve ser devolvida com `release`.
+pub struct UdpPortLease {
+    pub socket: UdpSocket,
+    pub port: u16,
+}
+
+/// Como as sessões recebem os blocos via UDP.
+pub enum UdpPorts {
+    /// Cada sessão abre um socket numa porta própria, emprestada do conjunto.
+    Pool(UdpPortPool),
+    /// Todas as sessões usam o mesmo socket, lido pelo `Reactor`, que distribui os datagramas pelo identificador de
+    /// sessão.
+    Shared { socket: UdpSocket, port: u16 },
+}
+
+impl UdpPorts {
+    /// Devolve a porta emprestada a uma sessão; não tem efeito no modo compartilhado, em que não há empréstimos.
+    pub fn release(&mut self, lease: UdpPortLease) {
+        if let UdpPorts::Pool(pool) = self {
+            pool.release(lease);
+        }
+    }
+}
+
+/// Portas UDP disponíveis para as sessões, dentro da faixa configurada. Cada sessão recebe uma porta livre, que volta
+/// ao conjunto quando a sessão termina.
+pub struct UdpPortPool {
+    range: RangeInclusive<u16>,
+    leased: Vec<bool>,
+    /// Próxima porta a ser tentada. As portas são percorridas em ordem circular, para que uma porta recém-devolvida
+    /// não seja reutilizada de imediato e receba datagramas atrasados da sessão anterior.
+    next_port: u16,
+    metrics: Arc<Metrics>,
+}
+
+impl UdpPortPool {
+    pub fn new(range: RangeInclusive<u16>, metrics: Arc<Metrics>) -> UdpPortPool {
+        let size = if range.is_empty() {
+            0
+        } else {
+            (range.end() - range.start()) as usize + 1
+        };
+
+        UdpPortPool {
+            next_port: *range.start(),
+            range,
+            leased: vec![false; size],
+            metrics,
+        }
+    }
+
+    /// Abre um socket UDP numa porta livre da faixa. Portas em que o bind falha, como as ocupadas por outros processos,
+    /// são ignoradas e a próxima é tentada; retorna um erro de `AddrNotAvailable` quando nenhuma porta da faixa pode
+    /// ser usada.
+    pub fn lease(&mut self) -> Result<UdpPortLease, Error> {
+        for _attempt in 0..self.leased.len() {
+            let port = self.next_port;
+            self.next_port = if port < *self.range.end() {
+                port + 1
+            } else {
+                *self.range.start()
+            };
+
+            let index = self.index(port);
+            if self.leased[index] {
+                continue;
+            }
+
+            let address = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port);
+            match UdpSocket::bind(address) {
+                Ok(socket) => {
+                    self.leased[index] = true;
+                    self.metrics.udp_port_acquired();
+                    return Ok(UdpPortLease { socket, port });
+                }
+                Err(e) => debug!(
+                    udp_port = port,
+                    "Falha ao fazer bind na porta UDP, tentando a próxima: {}", e
+                ),
+            }
+        }
+
+        Err(Error::new(
+            ErrorKind::AddrNotAvailable,
+            format!(
+                "Nenhuma porta UDP disponível na faixa {}-{}",
+                self.range.start(),
+                self.range.end()
+            ),
+        ))
+    }
+
+    /// Fecha o socket e devolve a porta ao conjunto. O socket já deve ter sido removido do poll.
+    pub fn release(&mut self, lease: UdpPortLease) {
+        let index = self.index(lease.port);
+        drop(lease.socket);
+        if self.leased[index] {
+            self.leased[index] = false;
+            self.metrics.udp_port_released();
+        }
+    }
+
+    fn index(&self, port: u16) -> usize {
+        (port - self.range.start()) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::net::{Ipv6Addr, SocketAddr, UdpSocket};
+    use std::sync::Arc;
+
+    use super::UdpPortPool;
+    use crate::metrics::Metrics;
+
+    // Cada teste usa uma faixa própria, já que os testes rodam em paralelo e abrem sockets de verdade.
+
+    #[test]
+    fn leases_ports_in_circular_order() {
+        let mut pool = UdpPortPool::new(46100..=46102, Arc::new(Metrics::default()));
+        let first = pool.lease().unwrap();
+        let second = pool.lease().unwrap();
+        assert_eq!((first.port, second.port), (46100, 46101));
+
+        // Uma porta devolvida só é reutilizada depois que as seguintes forem tentadas.
+        pool.release(first);
+        assert_eq!(pool.lease().unwrap().port, 46102);
+        assert_eq!(pool.lease().unwrap().port, 46100);
+    }
+
+    #[test]
+    fn reports_exhaustion_and_recovers_after_release() {
+        let metrics = Arc::new(Metrics::default());
+        let mut pool = UdpPortPool::new(46110..=46111, Arc::clone(&metrics));
+        let first = pool.lease().unwrap();
+        let _second = pool.lease().unwrap();
+        assert!(metrics
+            .render()
+            .contains("udp_transfer_udp_ports_in_use 2\n"));
+
+        let error = pool.lease().err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::AddrNotAvailable);
+
+        pool.release(first);
+        assert!(metrics
+            .render()
+            .contains("udp_transfer_udp_ports_in_use 1\n"));
+        assert_eq!(pool.lease().unwrap().port, 46110);
+    }
+
+    #[test]
+    fn skips_ports_used_by_other_processes() {
+        let _busy = UdpSocket::bind(SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 46121)).unwrap();
+        let mut pool = UdpPortPool::new(46120..=46122, Arc::new(Metrics::default()));
+        assert_eq!(pool.lease().unwrap().port, 46120);
+        assert_eq!(pool.lease().unwrap().port, 46122);
+        assert!(pool.lease().is_err());
+    }
+
+    #[test]
+    fn single_port_range_wraps_around() {
+        let mut pool = UdpPortPool::new(46130..=46130, Arc::new(Metrics::default()));
+        for _ in 0..3 {
+            let lease = pool.lease().unwrap();
+            assert_eq!(lease.port, 46130);
+            assert!(pool.lease().is_err());
+            pool.release(lease);
+        }
+    }
+
+    #[test]
+    fn range_ending_at_the_last_port_wraps_around() {
+        let mut pool = UdpPortPool::new(65534..=65535, Arc::new(Metrics::default()));
+        let first = pool.lease().unwrap();
+        let second = pool.lease().unwrap();
+        assert_eq!((first.port, second.port), (65534, 65535));
+        pool.release(first);
+        assert_eq!(pool.lease().unwrap().port, 65534);
+    }
+}
@@ -0,0 +1,201 @@
+//! Transferências completas entre `Client` e `Server` pela interface de loopback, com um retransmissor UDP entre os
+//! dois que perde e corrompe blocos.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use cliente::{Client, SendOptions};
+use common::{ChunkData, Message, MAX_DATAGRAM_SIZE};
+use servidor::{Server, ServerOptions, ShutdownHandle, TransferStats};
+
+const CHUNK_SIZE: usize = 1000;
+/// Bloco descartado pelo retransmissor na primeira vez em que é enviado.
+const LOST_CHUNK: u32 = 3;
+/// Bloco que, na primeira vez em que é enviado, chega ao servidor com bytes a mais que o tamanho anunciado.
+const OVERSIZED_CHUNK: u32 = 7;
+
+/// Servidor executado numa thread própria, com as estatísticas de cada arquivo recebido.
+struct TestServer {
+    address: SocketAddr,
+    metrics_address: SocketAddr,
+    shutdown: ShutdownHandle,
+    received: mpsc::Receiver<(PathBuf, TransferStats)>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl TestServer {
+    fn start(output_dir: PathBuf) -> TestServer {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (received_tx, received) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let options = ServerOptions {
+                output_dir,
+                metrics_address: Some("127.0.0.1:0".parse().unwrap()),
+                ..ServerOptions::default()
+            };
+            let server = Server::bind("127.0.0.1:0", options)
+                .unwrap()
+                .on_file_received(move |file| {
+                    let _ = received_tx.send((file.path.clone(), file.stats.clone()));
+                });
+            ready_tx
+                .send((
+                    server.local_addr().unwrap(),
+                    server.metrics_addr().unwrap(),
+                    server.shutdown_handle(),
+                ))
+                .unwrap();
+            server.run().unwrap();
+        });
+        let (address, metrics_address, shutdown) = ready_rx.recv().unwrap();
+        TestServer {
+            address,
+            metrics_address,
+            shutdown,
+            received,
+            thread,
+        }
+    }
+
+    /// Valor de `udp_transfer_datagrams_dropped_total` para o motivo informado.
+    fn dropped(&self, reason: &str) -> u64 {
+        let mut stream = TcpStream::connect(self.metrics_address).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let prefix = format!(
+            "udp_transfer_datagrams_dropped_total{{reason=\"{}\"}} ",
+            reason
+        );
+        response
+            .lines()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    fn stop(self) {
+        self.shutdown.shutdown().unwrap();
+        self.thread.join().unwrap();
+    }
+}
+
+/// Intermedeia uma única sessão: repassa a conexão TCP ao servidor, trocando a porta UDP da mensagem "Connection"
+/// pela de um retransmissor, que repassa os blocos ao servidor depois de perder `LOST_CHUNK` e aumentar
+/// `OVERSIZED_CHUNK` uma vez cada. Retorna o endereço em que o cliente deve se conectar.
+fn start_faulty_proxy(server: SocketAddr) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let relay = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let proxy_address = listener.local_addr().unwrap();
+    let relay_port = relay.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        let (mut client, _peer) = listener.accept().unwrap();
+        let mut upstream = TcpStream::connect(server).unwrap();
+
+        let mut client_reader = client.try_clone().unwrap();
+        let mut server_writer = upstream.try_clone().unwrap();
+        thread::spawn(move || io::copy(&mut client_reader, &mut server_writer));
+
+        // A primeira resposta do servidor é a mensagem "Connection", com a porta UDP logo depois do tipo.
+        let mut connection = [0; 20];
+        upstream.read_exact(&mut connection).unwrap();
+        assert_eq!(connection[1], 2, "Esperava uma mensagem \"Connection\"");
+        let port = u32::from_be_bytes([connection[2], connection[3], connection[4], connection[5]]);
+        let data_address = SocketAddr::new(server.ip(), port as u16);
+        connection[2..6].copy_from_slice(&(relay_port as u32).to_be_bytes());
+        client.write_all(&connection).unwrap();
+
+        thread::spawn(move || relay_chunks(relay, data_address));
+        let _ = io::copy(&mut upstream, &mut client);
+    });
+
+    proxy_address
+}
+
+fn relay_chunks(relay: UdpSocket, data_address: SocketAddr) {
+    let mut faults = HashSet::from([LOST_CHUNK, OVERSIZED_CHUNK]);
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+    loop {
+        let (bytes_read, _source) = match relay.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(_) => return,
+        };
+        let datagram = match Message::new(&buffer, bytes_read) {
+            Ok(Message::File(chunk)) if faults.remove(&chunk.sequence_number) => {
+                if chunk.sequence_number == LOST_CHUNK {
+                    continue;
+                }
+                let mut data = chunk.data;
+                data.extend_from_slice(&[0xff; 16]);
+                Message::File(ChunkData {
+                    payload_size: data.len() as u16,
+                    data,
+                    ..chunk
+                })
+                .encode()
+            }
+            _ => buffer[..bytes_read].to_vec(),
+        };
+        if relay.send_to(&datagram, data_address).is_err() {
+            return;
+        }
+    }
+}
+
+/// Diretório temporário exclusivo do teste, removido antes de ser criado.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("loopback-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn upload_recovers_from_a_lost_and_an_oversized_chunk() {
+    let dir = test_dir("upload");
+    let output_dir = dir.join("output");
+    let server = TestServer::start(output_dir.clone());
+    let proxy = start_faulty_proxy(server.address);
+
+    let contents: Vec<u8> = (0..20 * CHUNK_SIZE as u32 + 321)
+        .map(|byte| (byte % 251) as u8)
+        .collect();
+    let path = dir.join("dados.bin");
+    fs::write(&path, &contents).unwrap();
+
+    let mut client = Client::connect(proxy).unwrap();
+    let options = SendOptions {
+        chunk_size: CHUNK_SIZE,
+        timeout: Some(Duration::from_secs(10)),
+        ..SendOptions::default()
+    };
+    let stats = client.send_file(&path, &options).unwrap();
+    client.close().unwrap();
+
+    let (received_path, received_stats) = server
+        .received
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(received_path, output_dir.join("dados.bin"));
+    assert!(
+        fs::read(&received_path).unwrap() == contents,
+        "O arquivo recebido difere do enviado"
+    );
+    assert!(stats.retransmissions >= 2, "{:?}", stats);
+    assert_eq!(received_stats.file_size, contents.len() as u64);
+    assert_eq!(server.dropped("malformed"), 1);
+
+    server.stop();
+    fs::remove_dir_all(&dir).unwrap();
+}